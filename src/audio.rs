@@ -0,0 +1,328 @@
+use crate::synth::Synthesizer;
+use crate::machine::{
+    SOUND_AMP_ENABLE, SOUND_EXTENDED_PLAY, SOUND_FLEET_1, SOUND_FLEET_2, SOUND_FLEET_3,
+    SOUND_FLEET_4, SOUND_INVADER_DIE, SOUND_PLAYER_DIE, SOUND_SHOT, SOUND_UFO, SOUND_UFO_HIT,
+};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const CPU_HZ: u64 = 2_000_000;
+
+// Where mixed PCM ends up, 16 bit signed mono at SAMPLE_RATE
+pub trait AudioSink {
+    fn write_samples(&mut self, samples: &[i16]) -> io::Result<()>;
+
+    // Called once at the end so file sinks can fix up their headers
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Keeps everything in memory, handy for comparing renders
+impl AudioSink for Vec<i16> {
+    fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+// Anything that makes noise from the two sound latches (OUT 3 and OUT 5)
+pub trait SoundSource {
+    // Called at the instruction the latches changed on
+    fn latch(&mut self, sound1: u8, sound2: u8);
+    fn next_sample(&mut self) -> i32;
+}
+
+// Runs the sound sources in step with the CPU and sums them together
+pub struct Mixer {
+    sources: Vec<Box<dyn SoundSource>>,
+    sound1: u8,
+    sound2: u8,
+    phase: u64, // leftover CPU cycles, scaled by SAMPLE_RATE
+    buffer: Vec<i16>,
+}
+
+impl Mixer {
+    pub fn new() -> Mixer {
+        Mixer {
+            sources: Vec::new(),
+            sound1: 0,
+            sound2: 0,
+            phase: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn add_source(&mut self, source: Box<dyn SoundSource>) {
+        self.sources.push(source);
+    }
+
+    // Call after every CPU step with the cycles it took and the latches
+    // afterwards, produces however many samples that much time is worth
+    pub fn clock(&mut self, cycles: u8, sound1: u8, sound2: u8) {
+        if sound1 != self.sound1 || sound2 != self.sound2 {
+            self.sound1 = sound1;
+            self.sound2 = sound2;
+            for source in self.sources.iter_mut() {
+                source.latch(sound1, sound2);
+            }
+        }
+
+        self.phase += cycles as u64 * SAMPLE_RATE as u64;
+        while self.phase >= CPU_HZ {
+            self.phase -= CPU_HZ;
+            let sample = self.mix();
+            self.buffer.push(sample);
+        }
+    }
+
+    fn mix(&mut self) -> i16 {
+        let mut total: i32 = 0;
+        for source in self.sources.iter_mut() {
+            total += source.next_sample();
+        }
+
+        // The amplifier is switched off outside of a game
+        if self.sound1 & SOUND_AMP_ENABLE == 0 {
+            return 0;
+        }
+        total.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    }
+
+    // Hand everything rendered so far to the sink
+    pub fn drain_to(&mut self, sink: &mut dyn AudioSink) -> io::Result<()> {
+        sink.write_samples(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer::new()
+    }
+}
+
+// Which latch bit starts which of the standard 0.wav-9.wav samples
+const SAMPLE_TRIGGERS: [(u8, u8); 10] = [
+    (1, SOUND_UFO),
+    (1, SOUND_SHOT),
+    (1, SOUND_PLAYER_DIE),
+    (1, SOUND_INVADER_DIE),
+    (2, SOUND_FLEET_1),
+    (2, SOUND_FLEET_2),
+    (2, SOUND_FLEET_3),
+    (2, SOUND_FLEET_4),
+    (2, SOUND_UFO_HIT),
+    (1, SOUND_EXTENDED_PLAY),
+];
+
+struct Voice {
+    data: Vec<i16>,
+    position: Option<usize>, // None when not playing
+    looping: bool,
+    latch: u8, // 1 for OUT 3, 2 for OUT 5
+    bit: u8,
+    was_set: bool,
+}
+
+// Plays the recorded samples from a real cabinet. Each one starts on the
+// rising edge of its bit, the UFO loops for as long as its bit is held.
+pub struct SamplePlayer {
+    voices: Vec<Voice>,
+}
+
+impl SamplePlayer {
+    // Load 0.wav to 9.wav from `dir`, missing files just stay silent
+    pub fn load(dir: &Path) -> Result<SamplePlayer, String> {
+        let mut voices = Vec::new();
+        for (number, (latch, bit)) in SAMPLE_TRIGGERS.iter().enumerate() {
+            let path = dir.join(format!("{}.wav", number));
+            let data = match std::fs::read(&path) {
+                Ok(bytes) => read_wav(&bytes).map_err(|err| format!("{}: {}", path.display(), err))?,
+                Err(_) => Vec::new(),
+            };
+            voices.push(Voice {
+                data,
+                position: None,
+                looping: *bit == SOUND_UFO && *latch == 1,
+                latch: *latch,
+                bit: *bit,
+                was_set: false,
+            });
+        }
+        Ok(SamplePlayer { voices })
+    }
+}
+
+impl SoundSource for SamplePlayer {
+    fn latch(&mut self, sound1: u8, sound2: u8) {
+        for voice in self.voices.iter_mut() {
+            let value = if voice.latch == 1 { sound1 } else { sound2 };
+            let set = value & voice.bit != 0;
+
+            if set && !voice.was_set {
+                voice.position = Some(0);
+            } else if !set && voice.looping {
+                voice.position = None;
+            }
+            voice.was_set = set;
+        }
+    }
+
+    fn next_sample(&mut self) -> i32 {
+        let mut total = 0;
+        for voice in self.voices.iter_mut() {
+            if let Some(position) = voice.position {
+                match voice.data.get(position) {
+                    Some(sample) => {
+                        total += *sample as i32;
+                        voice.position = Some(position + 1);
+                    }
+                    None if voice.looping && !voice.data.is_empty() => {
+                        total += voice.data[0] as i32;
+                        voice.position = Some(1);
+                    }
+                    None => voice.position = None,
+                }
+            }
+        }
+        total
+    }
+}
+
+// Decode a PCM .wav into mono i16 at SAMPLE_RATE. Only plain 8 and 16 bit
+// PCM is supported, which is what the sample sets ship as.
+fn read_wav(bytes: &[u8]) -> Result<Vec<i16>, String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(String::from("not a WAV file"));
+    }
+
+    let mut format: Option<(u16, u32, u16)> = None; // channels, rate, bits
+    let mut data: Option<&[u8]> = None;
+
+    // Walk the chunks, they're word aligned
+    let mut i = 12;
+    while i + 8 <= bytes.len() {
+        let id = &bytes[i..i + 4];
+        let size = u32::from_le_bytes([bytes[i + 4], bytes[i + 5], bytes[i + 6], bytes[i + 7]]) as usize;
+        let body = &bytes[i + 8..(i + 8 + size).min(bytes.len())];
+
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let tag = u16::from_le_bytes([body[0], body[1]]);
+                if tag != 1 {
+                    return Err(format!("unsupported WAV format {}", tag));
+                }
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                format = Some((channels, rate, bits));
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        i += 8 + size + (size & 1);
+    }
+
+    let (channels, rate, bits) = format.ok_or("missing fmt chunk")?;
+    let data = data.ok_or("missing data chunk")?;
+    if channels == 0 || rate == 0 {
+        return Err(String::from("bad fmt chunk"));
+    }
+
+    // Down to mono, averaging the channels
+    let frame_size = channels as usize * (bits as usize / 8);
+    let mono: Vec<i16> = match bits {
+        8 => data
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                let sum: i32 = frame.iter().map(|b| (*b as i32 - 128) << 8).sum();
+                (sum / channels as i32) as i16
+            })
+            .collect(),
+        16 => data
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                let sum: i32 = frame.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as i32).sum();
+                (sum / channels as i32) as i16
+            })
+            .collect(),
+        _ => return Err(format!("unsupported sample size {}", bits)),
+    };
+
+    // Nearest neighbour resample, the originals are low-fi anyway
+    let length = mono.len() as u64 * SAMPLE_RATE as u64 / rate as u64;
+    Ok((0..length)
+        .map(|n| mono[(n * rate as u64 / SAMPLE_RATE as u64) as usize])
+        .collect())
+}
+
+// Writes 16 bit mono PCM to a .wav file, no sound card needed
+pub struct WavSink {
+    file: BufWriter<File>,
+    samples_written: u32,
+}
+
+impl WavSink {
+    pub fn create(path: &Path) -> io::Result<WavSink> {
+        let mut sink = WavSink {
+            file: BufWriter::new(File::create(path)?),
+            samples_written: 0,
+        };
+        // Sizes get patched in finish() once we know them
+        sink.write_header()?;
+        Ok(sink)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_size = self.samples_written * 2;
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(36 + data_size).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&1u16.to_le_bytes())?; // mono
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?; // byte rate
+        file.write_all(&2u16.to_le_bytes())?; // block align
+        file.write_all(&16u16.to_le_bytes())?; // bits per sample
+        file.write_all(b"data")?;
+        file.write_all(&data_size.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl AudioSink for WavSink {
+    fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written += samples.len() as u32;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+// Mixer fed from the sound latches, rendered into a .wav file. Without the
+// sample set the sounds are synthesized instead.
+pub fn open_audio(wav: &Path, samples: Option<&Path>) -> Result<(Mixer, WavSink), String> {
+    let mut mixer = Mixer::new();
+    match samples {
+        Some(dir) => mixer.add_source(Box::new(SamplePlayer::load(dir)?)),
+        None => mixer.add_source(Box::new(Synthesizer::new())),
+    }
+
+    let sink = WavSink::create(wav).map_err(|err| format!("Error creating {}: {}", wav.display(), err))?;
+    Ok((mixer, sink))
+}
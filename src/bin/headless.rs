@@ -0,0 +1,207 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use emu_8080::audio::AudioSink;
+use emu_8080::cpu;
+use emu_8080::frontend::{Frontend, Headless, InputScript};
+use emu_8080::machine;
+use emu_8080::options::{MachineOptions, MACHINE_USAGE};
+use emu_8080::video::{Video, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Runs the game with no window, for soak testing on machines without a
+// display. Everything it has to say ends up in the output directory.
+
+struct Options {
+    rom: String,
+    frames: u64,
+    until_pc: Option<u16>, // stop as soon as the CPU gets here
+    throttle: bool, // hold to 60 frames a second instead of flat out
+    script: InputScript,
+    out: PathBuf,
+    save_every: u64, // write every Nth frame as well as the last, 0 for just the last
+    machine: MachineOptions, // the same board, picture and sound options as the windowed build
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rom: String::from("invaders"),
+            frames: 3600, // a minute of attract mode
+            until_pc: None,
+            throttle: false,
+            script: InputScript::default(),
+            out: PathBuf::from("headless_out"),
+            save_every: 0,
+            machine: MachineOptions::default(),
+        }
+    }
+}
+
+fn usage() -> String {
+    format!("Usage: headless [--rom <file>] [--frames <n>] [--until-pc <hex>] [--throttle] [--script <file>] [--out <dir>] [--save-every <n>] {}", MACHINE_USAGE)
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing argument for '{}'", arg));
+        if options.machine.parse_arg(&arg, &mut value)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--rom" => options.rom = value()?,
+            "--frames" => {
+                let frames = value()?;
+                options.frames = frames.parse().map_err(|_| format!("Invalid frame count: {}", frames))?;
+            }
+            "--until-pc" => {
+                let pc = value()?;
+                let address = u16::from_str_radix(pc.trim_start_matches("0x"), 16).map_err(|_| format!("Invalid address: {}", pc))?;
+                options.until_pc = Some(address);
+            }
+            "--throttle" => options.throttle = true,
+            "--script" => {
+                let path = value()?;
+                let text = fs::read_to_string(&path).map_err(|err| format!("Error reading {}: {}", path, err))?;
+                options.script = InputScript::parse(&text)?;
+            }
+            "--out" => options.out = PathBuf::from(value()?),
+            "--save-every" => {
+                let frames = value()?;
+                options.save_every = frames.parse().map_err(|_| format!("Invalid frame count: {}", frames))?;
+            }
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+// Binary PPM, about the simplest image format there is
+fn write_ppm(path: &Path, frame: &[u32]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    write!(file, "P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT)?;
+    let pixels: Vec<u8> = frame
+        .iter()
+        .flat_map(|pixel| {
+            let [_, r, g, b] = pixel.to_be_bytes();
+            [r, g, b]
+        })
+        .collect();
+    file.write_all(&pixels)
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            println!("{}", usage());
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(options) {
+        println!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let rom = fs::read(&options.rom).map_err(|err| format!("Error reading {}: {}", options.rom, err))?;
+    fs::create_dir_all(&options.out).map_err(|err| format!("Error creating {}: {}", options.out.display(), err))?;
+    let mut log = File::create(options.out.join("log.txt")).map_err(|err| err.to_string())?;
+
+    let mut emu8080 = options.machine.machine();
+    for (address, byte) in rom.iter().enumerate() {
+        emu8080.bus.write_rom_mem(address as u16, *byte);
+    }
+
+    let mut audio = options.machine.audio()?;
+    let mut video = Video::new(options.machine.overlay());
+    let mut frontend = Headless::with_script(options.script);
+    let mut total_cycles: u64 = 0;
+    let mut errors = 0;
+    let started = Instant::now();
+
+    let stopped = 'running: loop {
+        if frontend.frames >= options.frames {
+            break 'running format!("ran {} frames", frontend.frames);
+        }
+        frontend.poll_input(&mut emu8080.bus.port.inputs);
+        let frame_start_time = Instant::now();
+
+        let frame = emu8080.bus.beam.frames();
+        while emu8080.bus.beam.frames() == frame {
+            let mixer = audio.as_mut().map(|(mixer, _)| mixer);
+            let frame_number = frontend.frames;
+            let result = machine::step(&mut emu8080, mixer, |event| {
+                let _ = writeln!(log, "frame {}: {}", frame_number, event);
+            });
+            let cycles = match result {
+                Ok(cycles) => cycles,
+                Err(err) => {
+                    errors += 1;
+                    let _ = writeln!(log, "frame {}: CPU error: {}", frontend.frames, err);
+                    err.cycles
+                }
+            };
+            total_cycles += cycles as u64;
+
+            if emu8080.is_halted() && !emu8080.interrupt_enabled() {
+                break 'running String::from("CPU halted with interrupts disabled");
+            }
+            if options.until_pc == Some(emu8080.get_pc()) {
+                break 'running format!("reached {:04X}", emu8080.get_pc());
+            }
+        }
+
+        frontend.present(video.render(emu8080.bus.beam.screen()))?;
+        if let Some((mixer, sink)) = audio.as_mut() {
+            mixer.drain_to(sink).map_err(|err| format!("Error writing audio: {}", err))?;
+        }
+        // Not is_multiple_of, that needs Rust 1.87
+        #[allow(clippy::manual_is_multiple_of)]
+        let save_frame = options.save_every != 0 && frontend.frames % options.save_every == 0;
+        if save_frame {
+            let path = options.out.join(format!("frame_{:06}.ppm", frontend.frames));
+            write_ppm(&path, video.frame()).map_err(|err| format!("Error writing {}: {}", path.display(), err))?;
+        }
+
+        if options.throttle {
+            let target_time = frame_start_time + Duration::from_secs_f64(1.0 / 60.0);
+            if let Some(remaining) = target_time.checked_duration_since(Instant::now()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    };
+
+    // Whatever's on screen at the end, even if we stopped mid frame
+    let final_frame = video.render(emu8080.bus.beam.screen());
+    write_ppm(&options.out.join("final.ppm"), final_frame).map_err(|err| format!("Error writing final.ppm: {}", err))?;
+    if let Some((mut mixer, mut sink)) = audio {
+        mixer.drain_to(&mut sink).map_err(|err| format!("Error writing audio: {}", err))?;
+        sink.finish().map_err(|err| format!("Error writing audio: {}", err))?;
+    }
+
+    let summary = format!(
+        "Stopped: {}\nFrames: {}\nCycles: {}\nCPU errors: {}\nWall time: {:.2}s\n",
+        stopped,
+        frontend.frames,
+        total_cycles,
+        errors,
+        started.elapsed().as_secs_f64()
+    );
+    let state = summary.clone() + &cpu::format_state(&emu8080);
+    fs::write(options.out.join("state.txt"), state).map_err(|err| format!("Error writing state.txt: {}", err))?;
+    print!("{}", summary);
+
+    if errors > 0 {
+        return Err(format!("{} CPU errors, see {}", errors, options.out.join("log.txt").display()));
+    }
+    Ok(())
+}
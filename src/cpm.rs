@@ -0,0 +1,110 @@
+use crate::cpu::{self, Bus, Register, RegisterPair, State8080};
+
+// Bare minimum CP/M environment for running the classic 8080 exerciser
+// programs (cpudiag, TST8080, 8080PRE, CPUTEST, 8080EXM). Only the console
+// output BDOS calls are handled, which is all those programs need.
+
+const TPA_START: u16 = 0x0100; // .COM files are always loaded here
+const BDOS_ENTRY: u16 = 0x0005;
+const BDOS_STUB: u16 = 0xFE00; // top of memory, programs set SP from 0x0006
+
+// Flat 64K of RAM with nothing on the I/O ports
+pub struct FlatMemory {
+    memory: Vec<u8>,
+}
+
+impl FlatMemory {
+    pub fn new() -> FlatMemory {
+        FlatMemory {
+            memory: vec![0; 0x10000],
+        }
+    }
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        FlatMemory::new()
+    }
+}
+
+impl Bus for FlatMemory {
+    fn read_byte(&self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+
+    fn port_in(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    fn port_out(&mut self, _port: u8, _value: u8) {}
+}
+
+// Set up memory the way CP/M would have left it for a freshly loaded .COM
+pub fn load_com(program: &[u8]) -> State8080<FlatMemory> {
+    let mut state = State8080::new(FlatMemory::new());
+
+    for (offset, byte) in program.iter().enumerate() {
+        state.write_mem(TPA_START.wrapping_add(offset as u16), *byte);
+    }
+
+    // CALL 5 lands on a JMP to a RET at the top of memory, so the program
+    // sees a sane BDOS address at 0x0006 and the trap below just has to
+    // print before the RET takes it home
+    state.write_mem(BDOS_ENTRY, 0xC3); // JMP
+    state.write_mem(BDOS_ENTRY + 1, BDOS_STUB as u8);
+    state.write_mem(BDOS_ENTRY + 2, (BDOS_STUB >> 8) as u8);
+    state.write_mem(BDOS_STUB, 0xC9); // RET
+
+    // The CCP calls programs with 0x0000 on the stack, so a plain RET warm boots
+    state.set_reg_pair(RegisterPair::SP, BDOS_STUB - 2);
+    state.set_pc(TPA_START);
+    state
+}
+
+// Run a .COM image until it warm boots by jumping to 0x0000 (or halts). Every character
+// the program prints is handed to `console`. Returns the cycles executed, or an
+// error if the program is still going after `max_cycles` or makes a bad BDOS call.
+pub fn run_com<F: FnMut(char)>(program: &[u8], max_cycles: u64, mut console: F) -> Result<u64, String> {
+    let mut state = load_com(program);
+    let mut cycles: u64 = 0;
+
+    // HLT with nothing to interrupt us is as good as an exit
+    while !state.is_halted() {
+        match state.get_pc() {
+            0x0000 => break,
+            BDOS_ENTRY => bdos_call(&state, &mut console)?,
+            _ => {}
+        }
+
+        if cycles >= max_cycles {
+            return Err(format!("Gave up after {} cycles at {:04X}", cycles, state.get_pc()));
+        }
+        cycles += cpu::emulate_8080_op(&mut state) as u64;
+    }
+
+    Ok(cycles)
+}
+
+fn bdos_call<F: FnMut(char)>(state: &State8080<FlatMemory>, console: &mut F) -> Result<(), String> {
+    match state.reg(Register::C) {
+        // C_WRITE, character in E
+        2 => console(state.reg(Register::E) as char),
+        // C_WRITESTR, '$' terminated string at DE. Found first, so a missing
+        // '$' doesn't print the whole of memory (forever)
+        9 => {
+            let start = state.reg_pair(RegisterPair::DE);
+            let length = (0..=0xFFFF)
+                .find(|offset| state.peek_mem(start.wrapping_add(*offset)) == b'$')
+                .ok_or_else(|| format!("No '$' after the string at {:04X}", start))?;
+            for offset in 0..length {
+                console(state.peek_mem(start.wrapping_add(offset)) as char);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,1403 @@
+use crate::disassemble::process_instruction;
+
+use std::fmt;
+use std::str::FromStr;
+
+// cool way to count cycles for an opcode I found here:
+// https://github.com/nav97/Intel-8080-Emulator/tree/master
+const CYCLES_8080: [u8; 256] = [
+    4, 10, 7, 5, 5, 5, 7, 4, 4, 10, 7, 5, 5, 5, 7, 4, //0x00..0x0f
+	4, 10, 7, 5, 5, 5, 7, 4, 4, 10, 7, 5, 5, 5, 7, 4, //0x10..0x1f
+	4, 10, 16, 5, 5, 5, 7, 4, 4, 10, 16, 5, 5, 5, 7, 4, //etc
+	4, 10, 13, 5, 10, 10, 10, 4, 4, 10, 13, 5, 5, 5, 7, 4,
+	
+	5, 5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5, //0x40..0x4f
+	5, 5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5,
+	5, 5, 5, 5, 5, 5, 7, 5, 5, 5, 5, 5, 5, 5, 7, 5,
+	7, 7, 7, 7, 7, 7, 7, 7, 5, 5, 5, 5, 5, 5, 7, 5,
+	
+	4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, //0x80..8x4f
+	4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4,
+	4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4,
+	4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4,
+	
+	5, 10, 10, 10, 11, 11, 7, 11, 5, 10, 10, 10, 11, 17, 7, 11, //0xc0..0xcf
+	5, 10, 10, 10, 11, 11, 7, 11, 5, 10, 10, 10, 11, 17, 7, 11,
+	5, 10, 10, 18, 11, 11, 7, 11, 5, 5, 10, 4, 11, 17, 7, 11,
+	5, 10, 10, 4, 11, 11, 7, 11, 5, 5, 10, 4, 11, 17, 7, 11,
+];
+
+// Conditional CALLs and RETs are listed above with their not-taken cost,
+// actually branching costs this much more (CALL 11/17, RET 5/11)
+const BRANCH_TAKEN_CYCLES: u8 = 6;
+
+pub struct ConditionCodes {
+    z: u8,
+    s: u8,
+    p: u8,
+    cy: u8,
+    ac: u8,
+}
+
+// Public view of ConditionCodes, and how they pack into the low byte of PSW
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags {
+    pub z: bool,
+    pub s: bool,
+    pub p: bool,
+    pub cy: bool,
+    pub ac: bool,
+}
+
+impl Flags {
+    // S Z 0 AC 0 P 1 CY, bit 1 always reads back as set
+    pub fn to_psw(self) -> u8 {
+        (self.s as u8) << 7
+            | (self.z as u8) << 6
+            | (self.ac as u8) << 4
+            | (self.p as u8) << 2
+            | 0x02
+            | self.cy as u8
+    }
+
+    pub fn from_psw(psw: u8) -> Flags {
+        Flags {
+            z: psw & 0x40 != 0,
+            s: psw & 0x80 != 0,
+            p: psw & 0x04 != 0,
+            cy: psw & 0x01 != 0,
+            ac: psw & 0x10 != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    A,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterPair {
+    BC,
+    DE,
+    HL,
+    SP,
+    PSW, // A and the flags
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "b" => Ok(Register::B),
+            "c" => Ok(Register::C),
+            "d" => Ok(Register::D),
+            "e" => Ok(Register::E),
+            "h" => Ok(Register::H),
+            "l" => Ok(Register::L),
+            "a" => Ok(Register::A),
+            _ => Err(format!("Invalid register: {}", name)),
+        }
+    }
+}
+
+impl FromStr for RegisterPair {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "bc" => Ok(RegisterPair::BC),
+            "de" => Ok(RegisterPair::DE),
+            "hl" => Ok(RegisterPair::HL),
+            "sp" => Ok(RegisterPair::SP),
+            "psw" => Ok(RegisterPair::PSW),
+            _ => Err(format!("Invalid register pair: {}", name)),
+        }
+    }
+}
+
+// Everything the CPU can see on the other side of its pins. A board hooks up
+// its memory map and I/O ports through this, see machine.rs for Space Invaders
+pub trait Bus {
+    fn read_byte(&self, address: u16) -> u8;
+    fn write_byte(&mut self, address: u16, value: u8);
+
+    // A read with no side effects at all, for debuggers and disassemblers.
+    // Boards whose reads do anything besides read (like recording a fault)
+    // should override this.
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.read_byte(address)
+    }
+
+    // IN/OUT instructions, value is whatever was in A
+    fn port_in(&mut self, port: u8) -> u8;
+    fn port_out(&mut self, port: u8, value: u8);
+
+    // Address of an access that hit nothing since the last call, if any.
+    // Boards that decode every address can leave this alone.
+    fn take_fault(&mut self) -> Option<u16> {
+        None
+    }
+}
+
+// Things a bad ROM can do to us. `pc` is always the address of the
+// instruction that caused it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    // One of the undocumented opcodes (0x08, 0xCB, 0xD9, 0xDD...)
+    UnknownOpcode { pc: u16, opcode: u8 },
+    // A push or pop took SP through the top/bottom of the address space
+    StackWrap { pc: u16, sp: u16 },
+    // The bus had nothing mapped at the address
+    BusFault { pc: u16, address: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {:02X} at {:04X}", opcode, pc),
+            CpuError::StackWrap { pc, sp } => write!(f, "stack wrapped with SP={:04X} at {:04X}", sp, pc),
+            CpuError::BusFault { pc, address } => write!(f, "bus fault accessing {:04X} at {:04X}", address, pc),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+// What step() hands back when the policy stops on an error. The instruction
+// still ran, so its cycles have to be counted like any other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepError {
+    pub cycles: u8,
+    pub error: CpuError,
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for StepError {}
+
+// A memory or I/O access made by an instruction, see State8080::set_watching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read { address: u16, value: u8 },
+    Write { address: u16, value: u8 },
+    In { port: u8, value: u8 },
+    Out { port: u8, value: u8 },
+}
+
+// What step() does when an instruction raises a CpuError
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    // Carry on like the silicon would (aliases execute, SP wraps, reads give 0)
+    #[default]
+    Ignore,
+    // Finish the instruction but hand the error back so a debugger can stop
+    Trap,
+    // Same as Trap, but also stop the CPU dead (halted, interrupts off)
+    Halt,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "ignore" => Ok(ErrorPolicy::Ignore),
+            "trap" => Ok(ErrorPolicy::Trap),
+            "halt" => Ok(ErrorPolicy::Halt),
+            _ => Err(format!("Invalid error policy: {}", name)),
+        }
+    }
+}
+
+pub struct State8080<B: Bus> {
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    h: u8,
+    l: u8,
+    sp: u16,
+    pc: u16,
+    pub bus: B, // memory and I/O for whichever board we're running
+    cc: ConditionCodes,
+    int_enable: u8,
+    ei_delay: bool, // EI only takes effect after the following instruction
+    int_request: Option<u8>, // instruction the board puts on the data bus for INTA
+    halted: bool, // set by HLT, only an interrupt gets us going again
+    op_pc: u16, // address of the instruction being executed
+    fault: Option<CpuError>, // first error raised by the current instruction
+    error_policy: ErrorPolicy,
+    watching: bool, // record accesses for the debugger
+    accesses: Vec<Access>, // made by the last instruction, when watching
+}
+
+impl<B: Bus> State8080<B> {
+    pub fn new(bus: B) -> Self {
+        State8080 {
+            a: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+            bus,
+            cc: ConditionCodes {
+                z: 0,
+                s: 0,
+                p: 0,
+                cy: 0,
+                ac: 0,
+            },
+            int_enable: 0,
+            ei_delay: false,
+            int_request: None,
+            halted: false,
+            op_pc: 0,
+            fault: None,
+            error_policy: ErrorPolicy::default(),
+            watching: false,
+            accesses: Vec::new(),
+        }
+    }
+
+    // Write a byte to memory at the specified address
+    pub fn write_mem(&mut self, address: u16, value: u8) {
+        self.bus.write_byte(address, value);
+    }
+
+    // Read a byte from memory at the specified address
+    pub fn read_mem(&self, address: u16) -> u8 {
+        self.bus.read_byte(address)
+    }
+
+    // Look at memory without the CPU noticing, see Bus::peek_byte
+    pub fn peek_mem(&self, address: u16) -> u8 {
+        self.bus.peek_byte(address)
+    }
+
+    pub fn reg(&self, reg: Register) -> u8 {
+        match reg {
+            Register::B => self.b,
+            Register::C => self.c,
+            Register::D => self.d,
+            Register::E => self.e,
+            Register::H => self.h,
+            Register::L => self.l,
+            Register::A => self.a,
+        }
+    }
+
+    pub fn set_reg(&mut self, reg: Register, value: u8) {
+        match reg {
+            Register::B => self.b = value,
+            Register::C => self.c = value,
+            Register::D => self.d = value,
+            Register::E => self.e = value,
+            Register::H => self.h = value,
+            Register::L => self.l = value,
+            Register::A => self.a = value,
+        }
+    }
+
+    pub fn reg_pair(&self, reg: RegisterPair) -> u16 {
+        match reg {
+            RegisterPair::BC => pair(self.b, self.c),
+            RegisterPair::DE => pair(self.d, self.e),
+            RegisterPair::HL => pair(self.h, self.l),
+            RegisterPair::SP => self.sp,
+            RegisterPair::PSW => pair(self.a, self.flags().to_psw()),
+        }
+    }
+
+    pub fn set_reg_pair(&mut self, reg: RegisterPair, value: u16) {
+        let (high, low) = ((value >> 8) as u8, value as u8);
+        match reg {
+            RegisterPair::BC => (self.b, self.c) = (high, low),
+            RegisterPair::DE => (self.d, self.e) = (high, low),
+            RegisterPair::HL => (self.h, self.l) = (high, low),
+            RegisterPair::SP => self.sp = value,
+            RegisterPair::PSW => {
+                self.a = high;
+                self.set_flags(Flags::from_psw(low));
+            }
+        }
+    }
+
+    pub fn flags(&self) -> Flags {
+        Flags {
+            z: self.cc.z != 0,
+            s: self.cc.s != 0,
+            p: self.cc.p != 0,
+            cy: self.cc.cy != 0,
+            ac: self.cc.ac != 0,
+        }
+    }
+
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.cc.z = flags.z as u8;
+        self.cc.s = flags.s as u8;
+        self.cc.p = flags.p as u8;
+        self.cc.cy = flags.cy as u8;
+        self.cc.ac = flags.ac as u8;
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self,value: u16) {
+        self.pc = value;
+    }
+
+    pub fn interrupt_enabled(&self) -> bool {
+        self.int_enable != 0
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // Assert the INT line. `opcode` is what the board drives onto the data bus
+    // when the CPU acknowledges, normally an RST. It's executed in place of the
+    // next fetch, so only single byte instructions make sense here. The request
+    // stays up until the CPU takes it.
+    pub fn request_interrupt(&mut self, opcode: u8) {
+        self.int_request = Some(opcode);
+    }
+
+    pub fn interrupt_pending(&self) -> bool {
+        self.int_request.is_some()
+    }
+
+    // What the RESET pin does: PC back to 0, interrupts off, out of HLT.
+    // Registers and memory are left as they were.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.int_enable = 0;
+        self.ei_delay = false;
+        self.int_request = None;
+        self.halted = false;
+    }
+
+    // Keep a list of the memory and I/O accesses each instruction makes.
+    // Off by default, it costs a little on every access.
+    pub fn set_watching(&mut self, watching: bool) {
+        self.watching = watching;
+        self.accesses.clear();
+    }
+
+    // Accesses made by the last instruction, empty unless watching
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = policy;
+    }
+
+}
+
+impl<B: Bus + Default> Default for State8080<B> {
+    fn default() -> Self {
+        State8080::new(B::default())
+    }
+}
+
+
+// Cycles burned per step while sitting in HLT
+const HALTED_CYCLES: u8 = 4;
+
+// Run one instruction and return the cycles it took, or the error it raised
+// (along with the cycles) if the error policy says to stop on it
+pub fn step<B: Bus>(state: &mut State8080<B>) -> Result<u8, StepError> {
+    let cycles = emulate_8080_op(state);
+
+    match state.fault.take() {
+        None => Ok(cycles),
+        Some(error) => match state.error_policy {
+            ErrorPolicy::Ignore => Ok(cycles),
+            ErrorPolicy::Trap => Err(StepError { cycles, error }),
+            ErrorPolicy::Halt => {
+                state.halted = true;
+                state.int_enable = 0u8;
+                Err(StepError { cycles, error })
+            }
+        },
+    }
+}
+
+// run an instruction and return the number of cycles, errors are recorded
+// for step() but otherwise ignored
+pub fn emulate_8080_op<B: Bus>(state: &mut State8080<B>) -> u8{
+    state.fault = None;
+    state.op_pc = state.pc;
+    // Anything the bus noticed before now (a stray read from outside the
+    // CPU, say) isn't this instruction's fault
+    let _ = state.bus.take_fault();
+    if state.watching {
+        state.accesses.clear();
+    }
+
+    // The instruction right after EI always runs before an interrupt is taken
+    let ei_delay = std::mem::replace(&mut state.ei_delay, false);
+
+    let opcode = match state.int_request {
+        // Interrupt acknowledge, the opcode comes off the data bus instead
+        // of memory and PC isn't advanced, so an RST pushes the address of
+        // the instruction we would have run
+        Some(opcode) if state.int_enable != 0 && !ei_delay => {
+            state.int_request = None;
+            state.int_enable = 0u8;
+            state.halted = false;
+            opcode
+        }
+        _ => {
+            // A halted CPU doesn't fetch anything, but time still passes
+            if state.halted {
+                return HALTED_CYCLES;
+            }
+
+            let opcode = state.bus.read_byte(state.pc);
+            state.pc = state.pc.wrapping_add(1); // Increment the program counter for the opcode
+            opcode
+        }
+    };
+
+    // Only fetch the operands the instruction really has, reading past a
+    // one byte instruction at the edge of memory shouldn't fault
+    let mut next_bytes = [0u8; 2];
+    for (offset, byte) in next_bytes.iter_mut().enumerate().take(operand_bytes(opcode)) {
+        *byte = state.bus.read_byte(state.pc.wrapping_add(offset as u16));
+    }
+    let d16 = (next_bytes[1] as u16) << 8 | next_bytes[0] as u16;
+
+    let mut cycles = CYCLES_8080[opcode as usize];
+
+    if is_undocumented(opcode) {
+        raise(state, CpuError::UnknownOpcode { pc: state.op_pc, opcode });
+    }
+
+    match opcode {
+        // 0x08-0x38 are undocumented, but behave as NOP on real silicon
+        0x00 | 0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => {/*NOP*/},
+        0x01 => {//LXI B,word
+            state.c = next_bytes[0];
+            state.b = next_bytes[1];
+            state.pc = state.pc.wrapping_add(2);
+        },
+        0x02 => { // STAX B
+            let bc = pair(state.b, state.c);
+            store(state, bc, state.a);
+        },
+        0x03 => { // INX B
+            let result = pair(state.b, state.c).wrapping_add(1);
+            state.b = (result >> 8) as u8;
+            state.c = result as u8;
+        },
+        0x04 => {state.b = inr(state, state.b)}, // INR B
+        0x05 => {state.b = dcr(state, state.b)}, // DCR B
+        0x06 => { //MVI B, D8
+            state.b = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0x07 => { // RLC
+            let carry = (state.a & 0x80) >> 7; // bit 7 goes to bit 0 and carry
+            state.a = (state.a << 1) | carry;
+            state.cc.cy = carry;
+        },
+        0x09 => {dad(state, pair(state.b, state.c))}, // DAD B
+        0x0A => { // LDAX B
+            let bc = pair(state.b, state.c);
+            state.a = load(state, bc);
+        },
+        0x0B => { // DCX B
+            let result = pair(state.b, state.c).wrapping_sub(1);
+            state.b = (result >> 8) as u8;
+            state.c = result as u8;
+        }
+        0x0C => {state.c = inr(state, state.c)}, // INR C
+        0x0D => {state.c = dcr(state, state.c)}, // DCR C
+        0x0E => { //MVI C, D8
+            state.c = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0x0F => { // RRC
+            let cy = state.a & 0x01; // bit 0 goes to bit 7 and carry
+            state.a = (state.a >> 1) | (cy << 7);
+            state.cc.cy = cy;
+        }
+
+        0x11 => {//LXI D,word
+            state.e = next_bytes[0];
+            state.d = next_bytes[1];
+            state.pc = state.pc.wrapping_add(2);
+        }
+        0x12 => { // STAX D
+            let de = pair(state.d, state.e);
+            store(state, de, state.a);
+        },
+        0x13 => {//INX D
+            let result = pair(state.d, state.e).wrapping_add(1);
+            state.d = (result >> 8) as u8;
+            state.e = result as u8;
+        }
+        0x14 => {state.d = inr(state, state.d)}, // INR D
+        0x15 => {state.d = dcr(state, state.d)}, // DCR D
+        0x16 => { // MVI D, D8
+            state.d = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        },
+        0x17 => { // RAL
+            let carry = (state.a & 0x80) >> 7;
+            state.a = (state.a << 1) | state.cc.cy;
+            state.cc.cy = carry;
+        },
+        0x19 => {dad(state, pair(state.d, state.e))}, // DAD D
+        0x1A => { //LDAX D
+            let de = pair(state.d, state.e);
+            state.a = load(state, de);
+        }
+        0x1B => { // DCX D
+            let result = pair(state.d, state.e).wrapping_sub(1);
+            state.d = (result >> 8) as u8;
+            state.e = result as u8;
+        },
+        0x1C => {state.e = inr(state, state.e)}, // INR E
+        0x1D => {state.e = dcr(state, state.e)}, // DCR E
+        0x1E => { // MVI E, D8
+            state.e = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        },
+        0x1F => { // RAR
+            let carry = state.a & 0x01;
+            state.a = (state.cc.cy << 7) | (state.a >> 1);
+            state.cc.cy = carry;
+        },
+
+        0x21 => {//LXI H,word
+            state.l = next_bytes[0];
+            state.h = next_bytes[1];
+            state.pc = state.pc.wrapping_add(2);
+        },
+        0x22 => { // SHLD adr
+            store(state, d16, state.l);
+            store(state, d16.wrapping_add(1), state.h);
+            state.pc = state.pc.wrapping_add(2);
+        },
+        0x23 => { //INX H
+            let result = pair(state.h, state.l).wrapping_add(1);
+            state.h = (result >> 8) as u8;
+            state.l = result as u8;
+        }
+        0x24 => {state.h = inr(state, state.h)}, // INR H
+        0x25 => {state.h = dcr(state, state.h)}, // DCR H
+        0x26 => { //MVI H, D8
+            state.h = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0x27 => { // DAA
+            let mut adjust = 0;
+            let mut carry = state.cc.cy == 1;
+
+            // Check if lower nibble of accumulator is greater than 9 or Auxiliary Carry Flag is set.
+            if state.a & 0x0F > 9 || state.cc.ac == 1 {
+                adjust |= 0x06;
+            }
+
+            // Check if accumulator is greater than 0x99 or Carry Flag is set
+            if state.a > 0x99 || carry {
+                adjust |= 0x60;
+                carry = true;
+            }
+
+            // AC reflects the carry out of bit 3 from the low nibble correction
+            state.cc.ac = ((state.a & 0x0F) + (adjust & 0x0F) > 0x0F) as u8;
+            state.a = state.a.wrapping_add(adjust);
+            update_state(state, state.a, carry, 0b1111);
+        },
+        0x29 => {dad(state, pair(state.h, state.l))}, //DAD H
+        0x2A => { // LHLD adr
+            state.l = load(state, d16);
+            state.h = load(state, d16.wrapping_add(1));
+            state.pc = state.pc.wrapping_add(2);
+        },
+        0x2B => { // DCX H
+            let result = pair(state.h, state.l).wrapping_sub(1);
+            state.h = (result >> 8) as u8;
+            state.l = result as u8;
+        },
+        0x2C => {state.l = inr(state, state.l)}, // INR L
+        0x2D => {state.l = dcr(state, state.l)}, // DCR L
+        0x2E => { // MVI L, D8
+            state.l = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        },
+        0x2F => { // CMA
+            state.a = !state.a;
+        },
+
+        0x31 => { // LXI SP,word
+            state.sp = d16;
+            state.pc = state.pc.wrapping_add(2);
+        }
+        0x32 => { // STA adr
+            store(state, d16, state.a);
+            state.pc = state.pc.wrapping_add(2);
+        }
+        0x33 => { // INX SP
+            state.sp = state.sp.wrapping_add(1);
+        },
+        0x34 => { // INR M
+            let hl = pair(state.h, state.l);
+            let value = load(state, hl);
+            let result = inr(state, value);
+            store(state, hl, result);
+        },
+        0x35 => { // DCR M
+            let hl = pair(state.h, state.l);
+            let value = load(state, hl);
+            let result = dcr(state, value);
+            store(state, hl, result);
+        }
+        0x36 => { //MVI M, D8
+            let hl = pair(state.h, state.l);
+            store(state, hl, next_bytes[0]);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0x37 => { // STC
+            state.cc.cy = 1u8;
+        },
+        0x39 => {dad(state, state.sp)}, // DAD SP
+        0x3A => { // LDA adr
+            state.a = load(state, d16);
+            state.pc = state.pc.wrapping_add(2);
+        }
+        0x3B => { // DCX SP
+            state.sp = state.sp.wrapping_sub(1);
+        },
+        0x3C => {state.a = inr(state, state.a)}, // INR A
+        0x3D => {state.a = dcr(state, state.a)}, // DCR A
+        0x3E => { // MVI A, D8
+            state.a = next_bytes[0];
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0x3F => { // CMC
+            state.cc.cy ^= 1;
+        },
+
+        0x40 => {/*MOV B,B*/},
+        0x41 => {state.b = state.c},//MOV B,C
+        0x42 => {state.b = state.d},//MOV B,D
+        0x43 => {state.b = state.e},//MOV B,E
+        0x44 => {state.b = state.h},//MOV B,H
+        0x45 => {state.b = state.l},//MOV B,L
+        0x46 => {state.b = read_m(state)},//MOV B,M
+        0x47 => {state.b = state.a},//MOV B,A
+        0x48 => {state.c = state.b},//MOV C,B
+        0x49 => {/*MOV C,C*/},
+        0x4A => {state.c = state.d},//MOV C,D
+        0x4B => {state.c = state.e},//MOV C,E
+        0x4C => {state.c = state.h},//MOV C,H
+        0x4D => {state.c = state.l},//MOV C,L
+        0x4E => {state.c = read_m(state)},//MOV C,M
+        0x4F => {state.c = state.a},//MOV C,A
+        0x50 => {state.d = state.b},//MOV D,B
+        0x51 => {state.d = state.c},//MOV D,C
+        0x52 => {/*MOV D,D*/},
+        0x53 => {state.d = state.e},//MOV D,E
+        0x54 => {state.d = state.h},//MOV D,H
+        0x55 => {state.d = state.l},//MOV D,L
+        0x56 => {state.d = read_m(state)},//MOV D,M
+        0x57 => {state.d = state.a},//MOV D,A
+        0x58 => {state.e = state.b},//MOV E,B
+        0x59 => {state.e = state.c},//MOV E,C
+        0x5A => {state.e = state.d},//MOV E,D
+        0x5B => {/*MOV E,E*/},
+        0x5C => {state.e = state.h},//MOV E,H
+        0x5D => {state.e = state.l},//MOV E,L
+        0x5E => {state.e = read_m(state)},//MOV E,M
+        0x5F => {state.e = state.a},//MOV E,A
+        0x60 => {state.h = state.b},//MOV H,B
+        0x61 => {state.h = state.c},//MOV H,C
+        0x62 => {state.h = state.d},//MOV H,D
+        0x63 => {state.h = state.e},//MOV H,E
+        0x64 => {/*MOV H,H*/},
+        0x65 => {state.h = state.l},//MOV H,L
+        0x66 => {state.h = read_m(state)},//MOV H,M
+        0x67 => {state.h = state.a},//MOV H,A
+        0x68 => {state.l = state.b},//MOV L,B
+        0x69 => {state.l = state.c},//MOV L,C
+        0x6A => {state.l = state.d},//MOV L,D
+        0x6B => {state.l = state.e},//MOV L,E
+        0x6C => {state.l = state.h},//MOV L,H
+        0x6D => {/*MOV L,L*/},
+        0x6E => {state.l = read_m(state)},//MOV L,M
+        0x6F => {state.l = state.a},//MOV L,A
+        0x70 => {write_m(state, state.b)},//MOV M,B
+        0x71 => {write_m(state, state.c)},//MOV M,C
+        0x72 => {write_m(state, state.d)},//MOV M,D
+        0x73 => {write_m(state, state.e)},//MOV M,E
+        0x74 => {write_m(state, state.h)},//MOV M,H
+        0x75 => {write_m(state, state.l)},//MOV M,L
+        0x76 => { // HLT
+            state.halted = true;
+        },
+        0x77 => {write_m(state, state.a)},//MOV M,A
+        0x78 => {state.a = state.b},//MOV A,B
+        0x79 => {state.a = state.c},//MOV A,C
+        0x7A => {state.a = state.d},//MOV A,D
+        0x7B => {state.a = state.e},//MOV A,E
+        0x7C => {state.a = state.h},//MOV A,H
+        0x7D => {state.a = state.l},//MOV A,L
+        0x7E => {state.a = read_m(state)},//MOV A,M
+        0x7F => {/*MOV A,A*/},
+
+        //register form arithmetic
+        0x80 => {add(state, state.b, 0)},//ADD B
+        0x81 => {add(state, state.c, 0)},//ADD C
+        0x82 => {add(state, state.d, 0)},//ADD D
+        0x83 => {add(state, state.e, 0)},//ADD E
+        0x84 => {add(state, state.h, 0)},//ADD H
+        0x85 => {add(state, state.l, 0)},//ADD L
+        0x86 => {let m = read_m(state); add(state, m, 0)},//ADD M
+        0x87 => {add(state, state.a, 0)},//ADD A
+        0x88 => {add(state, state.b, state.cc.cy)},//ADC B
+        0x89 => {add(state, state.c, state.cc.cy)},//ADC C
+        0x8A => {add(state, state.d, state.cc.cy)},//ADC D
+        0x8B => {add(state, state.e, state.cc.cy)},//ADC E
+        0x8C => {add(state, state.h, state.cc.cy)},//ADC H
+        0x8D => {add(state, state.l, state.cc.cy)},//ADC L
+        0x8E => {let m = read_m(state); add(state, m, state.cc.cy)},//ADC M
+        0x8F => {add(state, state.a, state.cc.cy)},//ADC A
+        0x90 => {state.a = sub(state, state.b, 0)},//SUB B
+        0x91 => {state.a = sub(state, state.c, 0)},//SUB C
+        0x92 => {state.a = sub(state, state.d, 0)},//SUB D
+        0x93 => {state.a = sub(state, state.e, 0)},//SUB E
+        0x94 => {state.a = sub(state, state.h, 0)},//SUB H
+        0x95 => {state.a = sub(state, state.l, 0)},//SUB L
+        0x96 => {let m = read_m(state); state.a = sub(state, m, 0)},//SUB M
+        0x97 => {state.a = sub(state, state.a, 0)},//SUB A
+        0x98 => {state.a = sub(state, state.b, state.cc.cy)},//SBB B
+        0x99 => {state.a = sub(state, state.c, state.cc.cy)},//SBB C
+        0x9A => {state.a = sub(state, state.d, state.cc.cy)},//SBB D
+        0x9B => {state.a = sub(state, state.e, state.cc.cy)},//SBB E
+        0x9C => {state.a = sub(state, state.h, state.cc.cy)},//SBB H
+        0x9D => {state.a = sub(state, state.l, state.cc.cy)},//SBB L
+        0x9E => {let m = read_m(state); state.a = sub(state, m, state.cc.cy)},//SBB M
+        0x9F => {state.a = sub(state, state.a, state.cc.cy)},//SBB A
+
+        //register form logic
+        0xA0 => {ana(state, state.b)},//ANA B
+        0xA1 => {ana(state, state.c)},//ANA C
+        0xA2 => {ana(state, state.d)},//ANA D
+        0xA3 => {ana(state, state.e)},//ANA E
+        0xA4 => {ana(state, state.h)},//ANA H
+        0xA5 => {ana(state, state.l)},//ANA L
+        0xA6 => {let m = read_m(state); ana(state, m)},//ANA M
+        0xA7 => {ana(state, state.a)},//ANA A
+        0xA8 => {xra(state, state.b)},//XRA B
+        0xA9 => {xra(state, state.c)},//XRA C
+        0xAA => {xra(state, state.d)},//XRA D
+        0xAB => {xra(state, state.e)},//XRA E
+        0xAC => {xra(state, state.h)},//XRA H
+        0xAD => {xra(state, state.l)},//XRA L
+        0xAE => {let m = read_m(state); xra(state, m)},//XRA M
+        0xAF => {xra(state, state.a)},//XRA A
+        0xB0 => {ora(state, state.b)},//ORA B
+        0xB1 => {ora(state, state.c)},//ORA C
+        0xB2 => {ora(state, state.d)},//ORA D
+        0xB3 => {ora(state, state.e)},//ORA E
+        0xB4 => {ora(state, state.h)},//ORA H
+        0xB5 => {ora(state, state.l)},//ORA L
+        0xB6 => {let m = read_m(state); ora(state, m)},//ORA M
+        0xB7 => {ora(state, state.a)},//ORA A
+        // CMP is a SUB that throws away the result
+        0xB8 => {sub(state, state.b, 0);},//CMP B
+        0xB9 => {sub(state, state.c, 0);},//CMP C
+        0xBA => {sub(state, state.d, 0);},//CMP D
+        0xBB => {sub(state, state.e, 0);},//CMP E
+        0xBC => {sub(state, state.h, 0);},//CMP H
+        0xBD => {sub(state, state.l, 0);},//CMP L
+        0xBE => {let m = read_m(state); sub(state, m, 0);},//CMP M
+        0xBF => {sub(state, state.a, 0);},//CMP A
+
+        0xC0 => {cycles += ret_if(state, state.cc.z == 0)}, // RNZ
+        0xC1 => { // POP B
+            (state.b, state.c) = pop(state);
+        }
+        0xC2 => {jump_if(state, state.cc.z == 0, d16)}, // JNZ adr
+        // 0xCB is an undocumented alias of JMP
+        0xC3 | 0xCB => {state.pc = d16}, // JMP adr
+        0xC4 => {cycles += call_if(state, state.cc.z == 0, d16)}, // CNZ adr
+        0xC5 => {push(state, state.b, state.c)}, // PUSH B
+        0xC6 => { // ADI D8
+            add(state, next_bytes[0], 0);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0xC7 => {rst(state, 0)}, // RST 0
+        0xC8 => {cycles += ret_if(state, state.cc.z != 0)}, // RZ
+        // 0xD9 is an undocumented alias of RET
+        0xC9 | 0xD9 => {ret(state)}, // RET
+        0xCA => {jump_if(state, state.cc.z != 0, d16)}, // JZ adr
+        0xCC => {cycles += call_if(state, state.cc.z != 0, d16)}, // CZ adr
+        // 0xDD, 0xED and 0xFD are undocumented aliases of CALL
+        0xCD | 0xDD | 0xED | 0xFD => {call(state, d16)}, // CALL adr
+        0xCE => { // ACI D8
+            add(state, next_bytes[0], state.cc.cy);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0xCF => {rst(state, 1)}, // RST 1
+
+        0xD0 => {cycles += ret_if(state, state.cc.cy == 0)}, // RNC
+        0xD1 => { //POP D
+            (state.d, state.e) = pop(state);
+        }
+        0xD2 => {jump_if(state, state.cc.cy == 0, d16)}, // JNC adr
+        0xD3 => {//OUT D8
+            let port = next_bytes[0];
+            output(state, port, state.a);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0xD4 => {cycles += call_if(state, state.cc.cy == 0, d16)}, // CNC adr
+        0xD5 => {push(state, state.d, state.e)}, //PUSH D
+        0xD6 => { // SUI D8
+            state.a = sub(state, next_bytes[0], 0);
+            state.pc = state.pc.wrapping_add(1);
+        },
+        0xD7 => {rst(state, 2)}, // RST 2
+        0xD8 => {cycles += ret_if(state, state.cc.cy != 0)}, // RC
+        0xDA => {jump_if(state, state.cc.cy != 0, d16)}, // JC adr
+        0xDB => { // IN D8
+            let port = next_bytes[0];
+            state.a = input(state, port);
+            state.pc = state.pc.wrapping_add(1); // Skip over the data byte
+        }
+        0xDC => {cycles += call_if(state, state.cc.cy != 0, d16)}, // CC adr
+        0xDE => { // SBI D8
+            state.a = sub(state, next_bytes[0], state.cc.cy);
+            state.pc = state.pc.wrapping_add(1);
+        },
+        0xDF => {rst(state, 3)}, // RST 3
+
+        0xE0 => {cycles += ret_if(state, state.cc.p == 0)}, // RPO
+        0xE1 => { //POP H
+            (state.h, state.l) = pop(state);
+        }
+        0xE2 => {jump_if(state, state.cc.p == 0, d16)}, // JPO adr
+        0xE3 => { // XTHL
+            let l = load(state, state.sp);
+            let h = load(state, state.sp.wrapping_add(1));
+            store(state, state.sp, state.l);
+            store(state, state.sp.wrapping_add(1), state.h);
+            state.l = l;
+            state.h = h;
+        },
+        0xE4 => {cycles += call_if(state, state.cc.p == 0, d16)}, // CPO adr
+        0xE5 => {push(state, state.h, state.l)}, //PUSH H
+        0xE6 => { // ANI D8
+            ana(state, next_bytes[0]);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0xE7 => {rst(state, 4)}, // RST 4
+        0xE8 => {cycles += ret_if(state, state.cc.p != 0)}, // RPE
+        0xE9 => { // PCHL
+            state.pc = pair(state.h, state.l);
+        },
+        0xEA => {jump_if(state, state.cc.p != 0, d16)}, // JPE adr
+        0xEB => { //XCHG
+            std::mem::swap(&mut state.h, &mut state.d);
+            std::mem::swap(&mut state.l, &mut state.e);
+        }
+        0xEC => {cycles += call_if(state, state.cc.p != 0, d16)}, // CPE adr
+        0xEE => { // XRI D8
+            xra(state, next_bytes[0]);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0xEF => {rst(state, 5)}, // RST 5
+
+        0xF0 => {cycles += ret_if(state, state.cc.s == 0)}, // RP
+        0xF1 => { // POP PSW
+            let (a, psw) = pop(state);
+            state.a = a;
+            state.set_flags(Flags::from_psw(psw));
+        }
+        0xF2 => {jump_if(state, state.cc.s == 0, d16)}, // JP adr
+        0xF3 => { // DI
+            state.int_enable = 0u8;
+        },
+        0xF4 => {cycles += call_if(state, state.cc.s == 0, d16)}, // CP adr
+        0xF5 => { // PUSH PSW
+            let psw = state.flags().to_psw();
+            push(state, state.a, psw);
+        }
+        0xF6 => { // ORI D8
+            ora(state, next_bytes[0]);
+            state.pc = state.pc.wrapping_add(1);
+        },
+        0xF7 => {rst(state, 6)}, // RST 6
+        0xF8 => {cycles += ret_if(state, state.cc.s != 0)}, // RM
+        0xF9 => { // SPHL
+            state.sp = pair(state.h, state.l);
+        },
+        0xFA => {jump_if(state, state.cc.s != 0, d16)}, // JM adr
+        0xFB => { // EI
+            state.int_enable = 1u8;
+            state.ei_delay = true;
+        },
+        0xFC => {cycles += call_if(state, state.cc.s != 0, d16)}, // CM adr
+        0xFE => { // CPI D8
+            sub(state, next_bytes[0], 0);
+            state.pc = state.pc.wrapping_add(1);
+        }
+        0xFF => {rst(state, 7)}, // RST 7
+    }
+
+    if let Some(address) = state.bus.take_fault() {
+        raise(state, CpuError::BusFault { pc: state.op_pc, address });
+    }
+
+    cycles
+
+}
+
+// How many bytes follow the opcode
+fn operand_bytes(opcode: u8) -> usize {
+    match opcode {
+        // LXI, SHLD/LHLD, STA/LDA
+        0x01 | 0x11 | 0x21 | 0x31 | 0x22 | 0x2A | 0x32 | 0x3A => 2,
+        // Jumps and calls, including the 0xCB, 0xDD, 0xED and 0xFD aliases
+        0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCB | 0xCC | 0xCD | 0xD2 | 0xD4 | 0xDA | 0xDC | 0xDD | 0xE2 | 0xE4 | 0xEA
+        | 0xEC | 0xED | 0xF2 | 0xF4 | 0xFA | 0xFC | 0xFD => 2,
+        // MVI
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => 1,
+        // Immediate arithmetic, OUT and IN
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE | 0xD3 | 0xDB => 1,
+        _ => 0,
+    }
+}
+
+fn is_undocumented(opcode: u8) -> bool {
+    matches!(opcode, 0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 | 0xCB | 0xD9 | 0xDD | 0xED | 0xFD)
+}
+
+// Only the first error of an instruction is kept
+fn raise<B: Bus>(state: &mut State8080<B>, error: CpuError) {
+    if state.fault.is_none() {
+        state.fault = Some(error);
+    }
+}
+
+// Build a 16 bit value out of a register pair
+fn pair(high: u8, low: u8) -> u16 {
+    (high as u16) << 8 | low as u16
+}
+
+// Read the byte pointed to by HL, aka the M "register"
+fn read_m<B: Bus>(state: &mut State8080<B>) -> u8 {
+    load(state, pair(state.h, state.l))
+}
+
+// Every data access the instructions make goes through these four, so the
+// debugger can see them when watching is on. Opcode fetches don't count.
+fn load<B: Bus>(state: &mut State8080<B>, address: u16) -> u8 {
+    let value = state.bus.read_byte(address);
+    if state.watching {
+        state.accesses.push(Access::Read { address, value });
+    }
+    value
+}
+
+fn store<B: Bus>(state: &mut State8080<B>, address: u16, value: u8) {
+    state.bus.write_byte(address, value);
+    if state.watching {
+        state.accesses.push(Access::Write { address, value });
+    }
+}
+
+fn input<B: Bus>(state: &mut State8080<B>, port: u8) -> u8 {
+    let value = state.bus.port_in(port);
+    if state.watching {
+        state.accesses.push(Access::In { port, value });
+    }
+    value
+}
+
+fn output<B: Bus>(state: &mut State8080<B>, port: u8, value: u8) {
+    state.bus.port_out(port, value);
+    if state.watching {
+        state.accesses.push(Access::Out { port, value });
+    }
+}
+
+fn write_m<B: Bus>(state: &mut State8080<B>, value: u8) {
+    store(state, pair(state.h, state.l), value);
+}
+
+// ADD/ADC/ADI/ACI, carry_in is 0 or the current carry flag
+fn add<B: Bus>(state: &mut State8080<B>, value: u8, carry_in: u8) {
+    let result = state.a as u16 + value as u16 + carry_in as u16;
+    state.cc.ac = ((state.a & 0x0F) + (value & 0x0F) + carry_in > 0x0F) as u8;
+    state.a = result as u8;
+    update_state(state, state.a, result > 0xFF, 0b1111);
+}
+
+// SUB/SBB/SUI/SBI/CMP/CPI, the caller decides whether to keep the result
+fn sub<B: Bus>(state: &mut State8080<B>, value: u8, borrow_in: u8) -> u8 {
+    let result = (state.a as u16).wrapping_sub(value as u16).wrapping_sub(borrow_in as u16);
+    // The ALU subtracts by adding the complement, so AC is the carry out of
+    // bit 3 of A + !value + !borrow, i.e. set when the low nibble did NOT borrow
+    state.cc.ac = ((state.a & 0x0F) + (!value & 0x0F) + (borrow_in ^ 1) > 0x0F) as u8;
+    // borrow happens if we went below zero
+    update_state(state, result as u8, result > 0xFF, 0b1111);
+    result as u8
+}
+
+// ANA sets AC to the OR of bit 3 of both operands, XRA and ORA clear it
+fn ana<B: Bus>(state: &mut State8080<B>, value: u8) {
+    state.cc.ac = ((state.a | value) & 0x08 != 0) as u8;
+    state.a &= value;
+    update_state(state, state.a, false, 0b1111);
+}
+
+fn xra<B: Bus>(state: &mut State8080<B>, value: u8) {
+    state.cc.ac = 0;
+    state.a ^= value;
+    update_state(state, state.a, false, 0b1111);
+}
+
+fn ora<B: Bus>(state: &mut State8080<B>, value: u8) {
+    state.cc.ac = 0;
+    state.a |= value;
+    update_state(state, state.a, false, 0b1111);
+}
+
+// INR and DCR leave the carry flag alone
+fn inr<B: Bus>(state: &mut State8080<B>, value: u8) -> u8 {
+    let result = value.wrapping_add(1);
+    state.cc.ac = (result & 0x0F == 0) as u8; // low nibble rolled over
+    update_state(state, result, false, 0b0111);
+    result
+}
+
+fn dcr<B: Bus>(state: &mut State8080<B>, value: u8) -> u8 {
+    let result = value.wrapping_sub(1);
+    // DCR adds 0xFF, so AC is set unless the low nibble had to borrow
+    state.cc.ac = (result & 0x0F != 0x0F) as u8;
+    update_state(state, result, false, 0b0111);
+    result
+}
+
+// DAD only ever touches the carry flag
+fn dad<B: Bus>(state: &mut State8080<B>, value: u16) {
+    let (result, carry) = pair(state.h, state.l).overflowing_add(value);
+    state.h = (result >> 8) as u8;
+    state.l = result as u8;
+    update_state(state, 0, carry, 0b1000);
+}
+
+fn jump_if<B: Bus>(state: &mut State8080<B>, condition: bool, address: u16) {
+    if condition {
+        state.pc = address;
+    } else {
+        state.pc = state.pc.wrapping_add(2);
+    }
+}
+
+fn call<B: Bus>(state: &mut State8080<B>, address: u16) {
+    // return address is past the two address bytes
+    let ret = state.pc.wrapping_add(2);
+    push(state, (ret >> 8) as u8, ret as u8);
+    state.pc = address;
+}
+
+// Returns the extra cycles spent if the call was taken
+fn call_if<B: Bus>(state: &mut State8080<B>, condition: bool, address: u16) -> u8 {
+    if condition {
+        call(state, address);
+        BRANCH_TAKEN_CYCLES
+    } else {
+        state.pc = state.pc.wrapping_add(2);
+        0
+    }
+}
+
+fn ret<B: Bus>(state: &mut State8080<B>) {
+    let (high, low) = pop(state);
+    state.pc = pair(high, low);
+}
+
+// Returns the extra cycles spent if the return was taken
+fn ret_if<B: Bus>(state: &mut State8080<B>, condition: bool) -> u8 {
+    if condition {
+        ret(state);
+        BRANCH_TAKEN_CYCLES
+    } else {
+        0
+    }
+}
+
+fn rst<B: Bus>(state: &mut State8080<B>, vector: u8) {
+    push(state, (state.pc >> 8) as u8, state.pc as u8);
+    state.pc = (vector as u16) * 8;
+}
+
+// Utility code
+pub fn print_state<B: Bus>(state: &State8080<B>) {
+    print!("{}", format_state(state));
+}
+
+// Same as print_state, for when it's going somewhere other than stdout
+pub fn format_state<B: Bus>(state: &State8080<B>) -> String {
+    let (_,inst) = process_instruction(state.peek_mem(state.pc), &[state.peek_mem(state.pc.wrapping_add(1)),state.peek_mem(state.pc.wrapping_add(2))]);
+    let mut out = String::new();
+    out += "=== State8080 ===\n";
+    out += &format!("A: 0x{:02X}   B: 0x{:02X}   C: 0x{:02X}\n", state.a, state.b, state.c);
+    out += &format!("D: 0x{:02X}   E: 0x{:02X}   H: 0x{:02X}   L: 0x{:02X}\n", state.d, state.e, state.h, state.l);
+    out += &format!("SP: 0x{:04X}   PC: 0x{:04X}\n", state.sp, state.pc);
+    out += &format!("CC - Z: {}  S: {}  P: {}  CY: {}  AC: {}\n",
+             state.cc.z, state.cc.s, state.cc.p, state.cc.cy, state.cc.ac);
+    out += &format!("Interrupt Enable: {}   Pending: {:?}   Halted: {}\n", state.int_enable, state.int_request, state.halted);
+    out += &format!("Opcode: {:02X}\n", state.peek_mem(state.pc));
+    out += &format!("Instruction: {}\n",inst);
+    out += "=================\n";
+    out
+}
+
+fn parity(value: u8) -> bool {
+    let mut bits: u8 = 0;
+        for i in 0..8 {
+            bits += (value >> i) & 1;
+        }
+        (bits & 1) == 0
+}
+
+fn update_state<B: Bus>(state: &mut State8080<B>, value: u8, carry: bool, flags_to_set: u8) {
+    
+    if flags_to_set & 0b0001 != 0 {
+        state.cc.z = (value == 0) as u8;
+    }
+    
+    if flags_to_set & 0b0010 != 0 {
+        state.cc.s = ((value & 0x80) != 0) as u8;
+    }
+    
+    if flags_to_set & 0b0100 != 0 {
+        state.cc.p = parity(value) as u8;
+    }
+    
+    if flags_to_set & 0b1000 != 0 {
+        state.cc.cy = carry as u8;
+    }
+}
+
+// Raise an interrupt with "RST interrupt_num" on the data bus. It's taken at
+// the next instruction boundary with interrupts enabled (11 cycles), which
+// also wakes the CPU from HLT. Since PC already points past the HLT, RET from
+// the handler carries on after it.
+pub fn generate_interrupt<B: Bus>(state: &mut State8080<B>, interrupt_num: u8) {
+    state.request_interrupt(0xC7 | (interrupt_num & 0x07) << 3);
+}
+
+fn push<B: Bus>(state: &mut State8080<B>, high_byte: u8, low_byte: u8) {
+    if state.sp < 2 {
+        raise(state, CpuError::StackWrap { pc: state.op_pc, sp: state.sp });
+    }
+
+    store(state, state.sp.wrapping_sub(1), high_byte);
+    store(state, state.sp.wrapping_sub(2), low_byte);
+
+    state.sp = state.sp.wrapping_sub(2);
+}
+
+// returns (high, low), the opposite of push
+fn pop<B: Bus>(state: &mut State8080<B>) -> (u8, u8) {
+    if state.sp > 0xFFFD {
+        raise(state, CpuError::StackWrap { pc: state.op_pc, sp: state.sp });
+    }
+
+    let low_byte = load(state, state.sp);
+    let high_byte = load(state, state.sp.wrapping_add(1));
+
+    state.sp = state.sp.wrapping_add(2);
+    (high_byte, low_byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpm::FlatMemory;
+
+    // Load `program` into RAM and run `steps` instructions of it
+    fn run(program: &[u8], steps: usize) -> State8080<FlatMemory> {
+        let mut state = State8080::default();
+        for (offset, byte) in program.iter().enumerate() {
+            state.write_mem(0x2000 + offset as u16, *byte);
+        }
+        state.set_pc(0x2000);
+        for _ in 0..steps {
+            emulate_8080_op(&mut state);
+        }
+        state
+    }
+
+    #[test]
+    fn daa_after_bcd_addition() {
+        // MVI A,09 / ADI 08 / DAA: 9 + 8 = 17, the half carry forces the +6
+        let state = run(&[0x3E, 0x09, 0xC6, 0x08, 0x27], 3);
+        assert_eq!((state.a, state.cc.cy, state.cc.ac), (0x17, 0, 0));
+
+        // MVI A,99 / ADI 01 / DAA: 99 + 1 = 100, both nibbles adjust and carry out
+        let state = run(&[0x3E, 0x99, 0xC6, 0x01, 0x27], 3);
+        assert_eq!((state.a, state.cc.cy, state.cc.ac, state.cc.z), (0x00, 1, 1, 1));
+
+        // MVI A,42 / ADI 15 / DAA: nothing to adjust
+        let state = run(&[0x3E, 0x42, 0xC6, 0x15, 0x27], 3);
+        assert_eq!((state.a, state.cc.cy, state.cc.ac), (0x57, 0, 0));
+    }
+
+    #[test]
+    fn auxiliary_carry() {
+        let cases: [(&[u8], u8); 9] = [
+            (&[0x3E, 0x0F, 0xC6, 0x01], 1), // ADI, carry out of bit 3
+            (&[0x3E, 0x07, 0xC6, 0x01], 0),
+            (&[0x3E, 0x15, 0xD6, 0x01], 1), // SUI, no borrow from bit 4 sets it
+            (&[0x3E, 0x10, 0xD6, 0x01], 0),
+            (&[0x3E, 0x08, 0xE6, 0x00], 1), // ANI, bit 3 of either operand
+            (&[0x3E, 0x00, 0xE6, 0x00], 0),
+            (&[0x3E, 0x0F, 0xEE, 0xFF], 0), // XRI always clears it
+            (&[0x3E, 0x0F, 0x3C], 1),       // INR A, 0F -> 10
+            (&[0x3E, 0x10, 0x3D], 0),       // DCR A, 10 -> 0F
+        ];
+        for (program, ac) in cases {
+            let state = run(program, 2);
+            assert_eq!(state.cc.ac, ac, "{:02X?}", program);
+        }
+    }
+
+    #[test]
+    fn conditional_call_and_ret_cycles() {
+        // LXI SP,3000 / XRA A / CNZ 200B / CZ 200B / NOP, then RNZ / RZ at 200B
+        let mut state = run(&[0x31, 0x00, 0x30, 0xAF, 0xC4, 0x0B, 0x20, 0xCC, 0x0B, 0x20, 0x00, 0xC0, 0xC8], 2);
+        let cycles: Vec<u8> = (0..4).map(|_| emulate_8080_op(&mut state)).collect();
+        // Not taken / taken for each
+        assert_eq!(cycles, [11, 17, 5, 11]);
+        assert_eq!((state.get_pc(), state.sp), (0x200A, 0x3000));
+    }
+
+    #[test]
+    fn hlt_waits_for_an_interrupt() {
+        // LXI SP,3000 / EI / HLT
+        let mut state = run(&[0x31, 0x00, 0x30, 0xFB, 0x76], 3);
+        assert!(state.is_halted());
+        // Sitting in HLT costs 4 cycles a step and goes nowhere
+        assert_eq!(emulate_8080_op(&mut state), 4);
+        assert_eq!(state.get_pc(), 0x2005);
+
+        generate_interrupt(&mut state, 1);
+        assert_eq!(emulate_8080_op(&mut state), 11);
+        assert!(!state.is_halted());
+        assert_eq!(state.get_pc(), 0x0008);
+        // The handler returns to the instruction after the HLT
+        assert_eq!((state.read_mem(0x2FFE), state.read_mem(0x2FFF)), (0x05, 0x20));
+    }
+
+    #[test]
+    fn interrupt_waits_one_instruction_after_ei() {
+        // LXI SP,3000 / EI / NOP / NOP
+        let mut state = run(&[0x31, 0x00, 0x30, 0xFB, 0x00, 0x00], 1);
+        // RST 1, held off while interrupts are disabled
+        state.request_interrupt(0xCF);
+        emulate_8080_op(&mut state);
+        assert!(state.interrupt_pending());
+        assert_eq!(state.get_pc(), 0x2004);
+
+        // The NOP after EI still runs, then the RST is taken
+        emulate_8080_op(&mut state);
+        assert_eq!(state.get_pc(), 0x2005);
+        emulate_8080_op(&mut state);
+        assert!(!state.interrupt_pending());
+        assert!(!state.interrupt_enabled());
+        assert_eq!(state.get_pc(), 0x0008);
+        assert_eq!((state.read_mem(0x2FFE), state.read_mem(0x2FFF)), (0x05, 0x20));
+    }
+
+    // LXI SP,0000 / PUSH B, which wraps the stack, under `policy`
+    fn wrap_stack(policy: ErrorPolicy) -> (State8080<FlatMemory>, Result<u8, StepError>) {
+        let mut state = run(&[0x31, 0x00, 0x00, 0xC5, 0x00], 1);
+        state.set_error_policy(policy);
+        let result = step(&mut state);
+        (state, result)
+    }
+
+    #[test]
+    fn ignore_policy_carries_on() {
+        let (state, result) = wrap_stack(ErrorPolicy::Ignore);
+        assert_eq!(result, Ok(11));
+        assert_eq!(state.sp, 0xFFFE);
+        assert!(!state.is_halted());
+
+        // The aliases just run, 0xCB is another JMP
+        let mut state = run(&[0xCB, 0x00, 0x30], 0);
+        assert_eq!(step(&mut state), Ok(10));
+        assert_eq!(state.get_pc(), 0x3000);
+    }
+
+    #[test]
+    fn trap_policy_reports_and_carries_on() {
+        let (mut state, result) = wrap_stack(ErrorPolicy::Trap);
+        let error = CpuError::StackWrap { pc: 0x2003, sp: 0x0000 };
+        assert_eq!(result, Err(StepError { cycles: 11, error }));
+        // The instruction still finished, and the CPU can go on from there
+        assert_eq!(state.sp, 0xFFFE);
+        assert!(!state.is_halted());
+        assert_eq!(step(&mut state), Ok(4));
+
+        let mut state = run(&[0xCB, 0x00, 0x30], 0);
+        state.set_error_policy(ErrorPolicy::Trap);
+        let error = CpuError::UnknownOpcode { pc: 0x2000, opcode: 0xCB };
+        assert_eq!(step(&mut state), Err(StepError { cycles: 10, error }));
+        assert_eq!(state.get_pc(), 0x3000);
+    }
+
+    #[test]
+    fn halt_policy_stops_the_cpu() {
+        let (mut state, result) = wrap_stack(ErrorPolicy::Halt);
+        assert_eq!(result.map_err(|err| err.cycles), Err(11));
+        assert!(state.is_halted());
+        assert!(!state.interrupt_enabled());
+        // Nothing runs from here on
+        assert_eq!(step(&mut state), Ok(4));
+        assert_eq!(state.get_pc(), 0x2004);
+    }
+
+    #[test]
+    fn psw_fixed_bits() {
+        // LXI SP,3000 / LXI B,FFFF / PUSH B / POP PSW / PUSH PSW
+        let state = run(&[0x31, 0x00, 0x30, 0x01, 0xFF, 0xFF, 0xC5, 0xF1, 0xF5], 5);
+        // Bits 3 and 5 come back clear and bit 1 set, whatever was popped
+        assert_eq!((state.read_mem(0x2FFE), state.read_mem(0x2FFF)), (0xD7, 0xFF));
+        assert_eq!(state.flags(), Flags { z: true, s: true, p: true, cy: true, ac: true });
+
+        // LXI SP,3000 / LXI B,0000 / PUSH B / POP PSW / PUSH PSW
+        let state = run(&[0x31, 0x00, 0x30, 0x01, 0x00, 0x00, 0xC5, 0xF1, 0xF5], 5);
+        assert_eq!((state.read_mem(0x2FFE), state.read_mem(0x2FFF)), (0x02, 0x00));
+        assert_eq!(state.reg_pair(RegisterPair::PSW), 0x0002);
+    }
+}
\ No newline at end of file
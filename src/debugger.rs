@@ -1,101 +1,577 @@
+use std::fmt;
 use std::io::{self,BufRead, Write};
-use crate::state8080::{State8080, self};
+use crate::cpu::{self, Access, Bus, Register, RegisterPair, State8080, StepError};
+use crate::disassemble::process_instruction;
+use crate::expression::Expr;
 
-//return a command to run and an optional secondary argument
-pub fn parse_command(emu8080: &mut State8080) -> i32 {
-    //TODO: Make this a 'manual' debugger mode
-    print!(">>>");
-    io::stdout().flush().unwrap(); // Flush the output buffer because we don't have a \n
+// How long cnd, next, finish and until keep going before deciding it's
+// never going to happen, a few seconds of game time
+const RUN_LIMIT: u32 = 10_000_000;
 
-    //println!("Next opcode to run {:02X}", emu8080.read_mem(emu8080.get_pc()));
-    let mut input = String::new();
+// What the emulator should do once a command has run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Stay, // keep reading commands
+    Resume, // back to running the game
+    Quit,
+}
 
-    // Read user input
-    io::stdin().lock().read_line(&mut input).unwrap();
+// What makes a breakpoint go off. Memory ranges are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Step, // every instruction, for breakpoints that are just a condition
+    Pc(u16), // about to run the instruction at this address
+    Read { start: u16, end: u16 },
+    Write { start: u16, end: u16 },
+    In(u8),
+    Out(u8),
+}
 
-    // Trim leading/trailing whitespaces and convert to lowercase
-    let input = input.trim().to_lowercase();
+impl Trigger {
+    fn matches(&self, access: &Access) -> bool {
+        match (self, access) {
+            (Trigger::Read { start, end }, Access::Read { address, .. }) => (*start..=*end).contains(address),
+            (Trigger::Write { start, end }, Access::Write { address, .. }) => (*start..=*end).contains(address),
+            (Trigger::In(watched), Access::In { port, .. }) => watched == port,
+            (Trigger::Out(watched), Access::Out { port, .. }) => watched == port,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trigger::Step => write!(f, "break"),
+            Trigger::Pc(address) => write!(f, "break at {:04X}", address),
+            Trigger::Read { start, end } => write!(f, "watch read {:04X}-{:04X}", start, end),
+            Trigger::Write { start, end } => write!(f, "watch write {:04X}-{:04X}", start, end),
+            Trigger::In(port) => write!(f, "watch in port {:02X}", port),
+            Trigger::Out(port) => write!(f, "watch out port {:02X}", port),
+        }
+    }
+}
+
+// An expression that has to be true, kept with its source for list
+pub struct Condition {
+    pub text: String,
+    pub expr: Expr,
+}
+
+// A trigger, plus an optional condition that has to hold as well
+pub struct Breakpoint {
+    pub trigger: Trigger,
+    pub condition: Option<Condition>,
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.condition {
+            Some(condition) => write!(f, "{} if {}", self.trigger, condition.text),
+            None => write!(f, "{}", self.trigger),
+        }
+    }
+}
+
+// Breakpoints and watchpoints, kept between trips into the debugger
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+        }
+    }
+
+    // Worth calling check() after every step at all?
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty()
+    }
+
+    // Call after each instruction, gives the reason if we should stop before
+    // the next one
+    pub fn check<B: Bus>(&self, emu8080: &State8080<B>) -> Option<String> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+
+        for (number, breakpoint) in self.breakpoints.iter().enumerate() {
+            let trigger = &breakpoint.trigger;
+            let hit = match trigger {
+                Trigger::Step => true,
+                Trigger::Pc(address) => emu8080.get_pc() == *address,
+                _ => emu8080.accesses().iter().any(|access| trigger.matches(access)),
+            };
+            let condition = match &breakpoint.condition {
+                Some(condition) => hit && condition.expr.is_true(emu8080),
+                None => hit,
+            };
+            if condition {
+                return Some(format!("Hit #{}: {}", number + 1, breakpoint));
+            }
+        }
+        None
+    }
+
+    // Read one command from stdin and carry it out. `step` runs one instruction
+    // on whatever the CPU is plugged into, so the rest of the machine keeps up
+    // with it (cpu::step on its own for a bare CPU).
+    pub fn parse_command<B: Bus, F>(&mut self, emu8080: &mut State8080<B>, step: F) -> Action
+    where
+        F: FnMut(&mut State8080<B>) -> Result<u8, StepError>,
+    {
+        print!(">>>");
+        io::stdout().flush().unwrap(); // Flush the output buffer because we don't have a \n
+
+        //println!("Next opcode to run {:02X}", emu8080.read_mem(emu8080.get_pc()));
+        let mut input = String::new();
+
+        // Read user input, end of input is as good as quit
+        if io::stdin().lock().read_line(&mut input).unwrap_or(0) == 0 {
+            return Action::Quit;
+        }
+
+        let action = self.run_command(emu8080, &input, step);
 
-    let mut iter = input.trim().split_whitespace();
+        // Only pay for access tracking while there's something watching
+        let watching = self
+            .breakpoints
+            .iter()
+            .any(|breakpoint| !matches!(breakpoint.trigger, Trigger::Pc(_) | Trigger::Step));
+        emu8080.set_watching(watching);
+        action
+    }
+
+    // Step once, Err with the reason if we have to stop
+    fn step_once<B: Bus, F>(&self, emu8080: &mut State8080<B>, step: &mut F) -> Result<(), String>
+    where
+        F: FnMut(&mut State8080<B>) -> Result<u8, StepError>,
+    {
+        step(emu8080).map_err(|err| err.to_string())?;
+        match self.check(emu8080) {
+            Some(reason) => Err(reason),
+            None => Ok(()),
+        }
+    }
+
+    // Keep stepping until `done` says so or a breakpoint goes off. `done` gets
+    // the state afterwards plus the opcode and SP from before the step.
+    fn run_until<B: Bus, F, D>(&self, emu8080: &mut State8080<B>, step: &mut F, mut done: D)
+    where
+        F: FnMut(&mut State8080<B>) -> Result<u8, StepError>,
+        D: FnMut(&State8080<B>, u8, u16) -> bool,
+    {
+        for _ in 0..RUN_LIMIT {
+            let opcode = emu8080.peek_mem(emu8080.get_pc());
+            let sp = emu8080.reg_pair(RegisterPair::SP);
+            if let Err(reason) = self.step_once(emu8080, step) {
+                println!("Stopped: {}", reason);
+                return;
+            }
+            if done(emu8080, opcode, sp) {
+                return;
+            }
+        }
+        println!("Gave up after {} instructions", RUN_LIMIT);
+    }
+
+    pub fn run_command<B: Bus, F>(&mut self, emu8080: &mut State8080<B>, input: &str, mut step: F) -> Action
+    where
+        F: FnMut(&mut State8080<B>) -> Result<u8, StepError>,
+    {
+        // Trim leading/trailing whitespaces and convert to lowercase
+        let input = input.trim().to_lowercase();
+
+        let mut iter = input.split_whitespace();
 
-    if let Some(cmd) = iter.next() {
-        match cmd {
-            "quit" => return -1,
+        if let Some(cmd) = iter.next() {
+            // Everything after the command, for the ones that take an expression
+            let rest = input[cmd.len()..].trim();
 
-            // run for n instructions, return 1 for run
-            "run" => {
-                if let Some(arg) = iter.next() {
-                    // Handle the "run" command with the specified argument
-                    // Add your code here to handle the argument as desired
-                    println!("Running program for {} lines", arg);
-                    let runcmd = arg.parse::<i32>().unwrap_or(0);
+            match cmd {
+                "quit" => return Action::Quit,
 
-                    for _ in 1..runcmd {
-                        state8080::emulate_8080_op(emu8080);
+                "continue" => return Action::Resume,
+
+                // run for n instructions
+                "run" => {
+                    match iter.next().map(|count| count.parse::<u32>()) {
+                        Some(Ok(count)) => {
+                            println!("Running program for {} lines", count);
+                            for _ in 0..count {
+                                if let Err(reason) = self.step_once(emu8080, &mut step) {
+                                    println!("Stopped: {}", reason);
+                                    break;
+                                }
+                            }
+                        }
+                        Some(Err(_)) => println!("Usage: run <n>"),
+                        None => println!("Missing argument for 'run' command"),
                     }
-                    // Return the desired integer value
-                    return 0;
-                } else {
-                    println!("Missing argument for 'run' command");
-                    // Return an error code or handle the missing argument case as desired
-                    return 0;
+                    return Action::Stay;
                 }
-            }
-            // Run until some condition is met
-            "cnd" => {
-                if let Some(arg) = iter.next() {
-                    // Split the argument into parts using the logic operator as the separator
-                    let parts: Vec<&str> = arg.splitn(2, |c| c == '=' || c == '<' || c == '>').collect();
-                    if parts.len() == 2 {
-                        let register = parts[0].trim().chars().next().expect("string is empty");
-                        let condition = parts[1].trim();
-                        let value: u8 = match condition.parse() {
-                            Ok(value) => value,
-                            Err(_) => {
-                                println!("Invalid condition value: {}", condition);
-                                return 0;
+                // Run until some condition is met
+                "cnd" => {
+                    if rest.is_empty() {
+                        println!("Missing argument for 'cnd' command");
+                        return Action::Stay;
+                    }
+                    let condition: Expr = match rest.parse() {
+                        Ok(condition) => condition,
+                        Err(err) => {
+                            println!("{}", err);
+                            return Action::Stay;
+                        }
+                    };
+
+                    if !condition.is_true(emu8080) {
+                        self.run_until(emu8080, &mut step, |state, _, _| condition.is_true(state));
+                    }
+                    return Action::Stay;
+                }
+                // break <addr> [if <condition>], or break if <condition>
+                "break" => {
+                    let (spec, condition) = match split_condition(rest) {
+                        Ok(split) => split,
+                        Err(err) => {
+                            println!("{}", err);
+                            return Action::Stay;
+                        }
+                    };
+                    let trigger = match spec {
+                        "" if condition.is_some() => Some(Trigger::Step),
+                        address => parse_hex(address).map(Trigger::Pc),
+                    };
+                    match trigger {
+                        Some(trigger) => self.add(trigger, condition),
+                        None => println!("Usage: break <addr> [if <condition>] or break if <condition>"),
+                    }
+                    return Action::Stay;
+                }
+                // watch read|write <addr>[-<addr>], watch in|out <port>, either
+                // can have an if <condition> on the end
+                "watch" => {
+                    let (spec, condition) = match split_condition(rest) {
+                        Ok(split) => split,
+                        Err(err) => {
+                            println!("{}", err);
+                            return Action::Stay;
+                        }
+                    };
+                    let mut iter = spec.split_whitespace();
+                    let trigger = match (iter.next(), iter.next()) {
+                        (Some(kind @ ("read" | "write")), Some(range)) => parse_range(range).map(|(start, end)| {
+                            if kind == "read" {
+                                Trigger::Read { start, end }
+                            } else {
+                                Trigger::Write { start, end }
+                            }
+                        }),
+                        (Some("in"), Some(port)) => parse_port(port).map(Trigger::In),
+                        (Some("out"), Some(port)) => parse_port(port).map(Trigger::Out),
+                        _ => None,
+                    };
+                    match trigger {
+                        Some(trigger) => self.add(trigger, condition),
+                        None => println!("Usage: watch read|write <addr>[-<addr>] or watch in|out <port>"),
+                    }
+                    return Action::Stay;
+                }
+                "list" => {
+                    if self.breakpoints.is_empty() {
+                        println!("No breakpoints or watchpoints");
+                    }
+                    for (number, breakpoint) in self.breakpoints.iter().enumerate() {
+                        println!("#{}: {}", number + 1, breakpoint);
+                    }
+                    return Action::Stay;
+                }
+                // delete <n> removes one, plain delete removes them all
+                "delete" => {
+                    match iter.next() {
+                        None => self.breakpoints.clear(),
+                        Some(arg) => match arg.parse::<usize>() {
+                            Ok(number) if (1..=self.breakpoints.len()).contains(&number) => {
+                                println!("Deleted #{}: {}", number, self.breakpoints.remove(number - 1));
+                            }
+                            _ => println!("No breakpoint #{}", arg),
+                        },
+                    }
+                    return Action::Stay;
+                }
+                "status" => {
+                    cpu::print_state(emu8080);
+                    return Action::Stay;
+                }
+                // Step, but run a CALL or RST through to where it returns to
+                "next" => {
+                    let pc = emu8080.get_pc();
+                    let sp = emu8080.reg_pair(RegisterPair::SP);
+                    match call_length(emu8080.peek_mem(pc)) {
+                        // SP has to be back where it was too, in case the
+                        // routine ends up calling itself
+                        Some(length) => {
+                            let return_address = pc.wrapping_add(length);
+                            self.run_until(emu8080, &mut step, |state, _, _| {
+                                state.get_pc() == return_address && state.reg_pair(RegisterPair::SP) >= sp
+                            });
+                        }
+                        None => {
+                            if let Err(reason) = self.step_once(emu8080, &mut step) {
+                                println!("Stopped: {}", reason);
                             }
-                        };
-    
-                        // Perform the desired comparison based on the register and condition
-                        
-                        while State8080::get_reg(emu8080, register) != value {
-                            state8080::emulate_8080_op(emu8080);
                         }
-                            
-    
-                        return 0;
-                    } else {
-                        println!("Invalid condition format: {}", arg);
-                        return 0;
                     }
-                } else {
-                    println!("Missing argument for 'cnd' command");
-                    return 0;
+                    disassemble(emu8080, emu8080.get_pc(), 1);
+                    return Action::Stay;
+                }
+                // Run until the current subroutine returns, which is the first
+                // return that takes SP above where it is now. Interrupt handlers
+                // and anything it calls return below that.
+                "finish" => {
+                    let start_sp = emu8080.reg_pair(RegisterPair::SP);
+                    self.run_until(emu8080, &mut step, |state, opcode, _| {
+                        is_return(opcode) && state.reg_pair(RegisterPair::SP) > start_sp
+                    });
+                    disassemble(emu8080, emu8080.get_pc(), 1);
+                    return Action::Stay;
+                }
+                // until <addr>, run until PC gets there
+                "until" => {
+                    match iter.next().and_then(parse_hex) {
+                        Some(address) => {
+                            self.run_until(emu8080, &mut step, |state, _, _| state.get_pc() == address);
+                            disassemble(emu8080, emu8080.get_pc(), 1);
+                        }
+                        None => println!("Usage: until <addr>"),
+                    }
+                    return Action::Stay;
+                }
+                // x/N <addr>, hex and ASCII dump of N bytes (16 if there's no /N)
+                x if x == "x" || x.starts_with("x/") => {
+                    let count = match x.strip_prefix("x/") {
+                        Some(count) => count.parse::<usize>().ok(),
+                        None => Some(16),
+                    };
+                    match (count, iter.next().and_then(parse_hex)) {
+                        (Some(count), Some(address)) => dump_memory(emu8080, address, count),
+                        _ => println!("Usage: x/N <addr>"),
+                    }
+                    return Action::Stay;
+                }
+                // set mem <addr> <value>, set reg <name> <value>, values are hex like addresses
+                "set" => {
+                    let (what, target, value) = (iter.next(), iter.next(), iter.next());
+                    let value = match value {
+                        Some(text) => match parse_hex(text) {
+                            Some(value) => value,
+                            None => {
+                                println!("Invalid value: {}", text);
+                                return Action::Stay;
+                            }
+                        },
+                        None => {
+                            println!("Usage: set mem <addr> <value> or set reg <name> <value>");
+                            return Action::Stay;
+                        }
+                    };
+                    // Rather than quietly dropping the top half
+                    let byte = u8::try_from(value).map_err(|_| format!("{:X} doesn't fit in a byte", value));
+
+                    match (what, target) {
+                        (Some("mem"), Some(address)) => match (parse_hex(address), byte) {
+                            (None, _) => println!("Invalid address: {}", address),
+                            (_, Err(err)) => println!("{}", err),
+                            (Some(address), Ok(byte)) => emu8080.write_mem(address, byte),
+                        },
+                        (Some("reg"), Some("pc")) => emu8080.set_pc(value),
+                        (Some("reg"), Some(name)) => {
+                            if let Ok(reg) = name.parse::<Register>() {
+                                match byte {
+                                    Ok(byte) => emu8080.set_reg(reg, byte),
+                                    Err(err) => println!("{}", err),
+                                }
+                            } else if let Ok(reg) = name.parse::<RegisterPair>() {
+                                emu8080.set_reg_pair(reg, value);
+                            } else {
+                                println!("Unknown register: {}", name);
+                            }
+                        }
+                        _ => println!("Usage: set mem <addr> <value> or set reg <name> <value>"),
+                    }
+                    return Action::Stay;
+                }
+                // dis [addr] [n], disassemble n instructions (10 by default) from addr or PC
+                "dis" => {
+                    let address = match iter.next() {
+                        Some(address) => parse_hex(address),
+                        None => Some(emu8080.get_pc()),
+                    };
+                    let count = match iter.next() {
+                        Some(count) => count.parse::<usize>().ok(),
+                        None => Some(10),
+                    };
+                    match (address, count) {
+                        (Some(address), Some(count)) => disassemble(emu8080, address, count),
+                        _ => println!("Usage: dis [addr] [n]"),
+                    }
+                    return Action::Stay;
+                }
+                // stack [n], the top n words on the stack (8 by default)
+                "stack" => {
+                    match iter.next().map_or(Some(8), |count| count.parse::<u16>().ok()) {
+                        Some(count) => print_stack(emu8080, count),
+                        None => println!("Usage: stack [n]"),
+                    }
+                    return Action::Stay;
+                }
+                "help" => {
+                    println!("Available commands:");
+                    println!("run <n> - Run the program for n instructions");
+                    println!("cnd <condition> - Run until the condition is true");
+                    println!("status - Display current register/system status");
+                    println!("next - Step, running over CALLs and RSTs");
+                    println!("finish - Run until the current subroutine returns");
+                    println!("until <addr> - Run until PC reaches addr");
+                    println!("x/N <addr> - Dump N bytes of memory");
+                    println!("set mem <addr> <value> - Change a byte of memory");
+                    println!("set reg <name> <value> - Change a register, pair or pc");
+                    println!("dis [addr] [n] - Disassemble n instructions from addr (default PC)");
+                    println!("stack [n] - Show the top n words on the stack");
+                    println!("break <addr> - Stop before running the instruction at addr");
+                    println!("break if <condition> - Stop once the condition is true");
+                    println!("watch read|write <addr>[-<addr>] - Stop after an access to memory");
+                    println!("watch in|out <port> - Stop after an IN or OUT on a port");
+                    println!("  break and watch take an 'if <condition>' on the end too");
+                    println!("list - Show breakpoints and watchpoints");
+                    println!("delete [n] - Delete breakpoint n, or all of them");
+                    println!("continue - Go back to running the game");
+                    println!("quit - Quit the program");
+                    println!("help - Display information about the commands");
+                    println!("Addresses and ports are hex");
+                    println!("Conditions look like: pc==0x0ADD && a>3, [hl]!=0, byte[0x20E7]==1, cy || z");
+                    return Action::Stay;
+                }
+                _ => {
+                    println!("Unknown command: {}", cmd);
+                    return Action::Stay;
                 }
             }
-            "status" => {
-                state8080::print_state(emu8080);
-                //return 1 to do nothing
-                return 1;
-            }
-            "help" => {
-                println!("Available commands:");
-                println!("quit - Quit the program");
-                println!("run <n> - Run the program for n instructions");
-                println!("status - Display current register/system status");
-                println!("help - Display information about the commands");
-                // Return 1 to indicate successful execution of the "help" command
-                return 1;
-            }
-            _ => {
-                println!("Unknown command: {}", cmd);
-                // Return an error code or handle the unknown command case as desired
-                return 1;
-            }
         }
+
+        // empty line, do nothing
+        Action::Stay
+    }
+
+    fn add(&mut self, trigger: Trigger, condition: Option<Condition>) {
+        let breakpoint = Breakpoint { trigger, condition };
+        println!("#{}: {}", self.breakpoints.len() + 1, breakpoint);
+        self.breakpoints.push(breakpoint);
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
+
+// How far past a CALL (taken or not) or RST execution carries on afterwards
+fn call_length(opcode: u8) -> Option<u16> {
+    match opcode {
+        0xCD | 0xDD | 0xED | 0xFD => Some(3), // CALL and its aliases
+        0xC4 | 0xCC | 0xD4 | 0xDC | 0xE4 | 0xEC | 0xF4 | 0xFC => Some(3), // Ccc
+        op if op & 0xC7 == 0xC7 => Some(1), // RST n
+        _ => None,
+    }
+}
+
+// RET, its alias and the Rcc family. A conditional one that isn't taken
+// leaves SP alone, so callers check SP as well.
+fn is_return(opcode: u8) -> bool {
+    opcode == 0xC9 || opcode == 0xD9 || opcode & 0xC7 == 0xC0
+}
+
+fn dump_memory<B: Bus>(emu8080: &State8080<B>, address: u16, count: usize) {
+    for line_start in (0..count).step_by(16) {
+        let line_address = address.wrapping_add(line_start as u16);
+        let bytes: Vec<u8> = (0..16.min(count - line_start))
+            .map(|offset| emu8080.peek_mem(line_address.wrapping_add(offset as u16)))
+            .collect();
+
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+        println!("{:04X}: {:<47}  {}", line_address, hex.join(" "), ascii);
+    }
+}
+
+fn disassemble<B: Bus>(emu8080: &State8080<B>, address: u16, count: usize) {
+    let mut address = address;
+    for _ in 0..count {
+        let bytes = [
+            emu8080.peek_mem(address),
+            emu8080.peek_mem(address.wrapping_add(1)),
+            emu8080.peek_mem(address.wrapping_add(2)),
+        ];
+        let (length, text) = process_instruction(bytes[0], &bytes[1..]);
+        let hex: Vec<String> = bytes[..length].iter().map(|byte| format!("{:02X}", byte)).collect();
+        let marker = if address == emu8080.get_pc() { ">" } else { " " };
+        println!("{} {:04X}: {:<8}  {}", marker, address, hex.join(" "), text);
+        address = address.wrapping_add(length as u16);
+    }
+}
+
+fn print_stack<B: Bus>(emu8080: &State8080<B>, count: u16) {
+    let sp = emu8080.reg_pair(RegisterPair::SP);
+    // 0x8000 words is all of memory, any more would just go round again
+    for i in 0..count.min(0x8000) {
+        let address = sp.wrapping_add(i * 2);
+        let low = emu8080.peek_mem(address) as u16;
+        let high = emu8080.peek_mem(address.wrapping_add(1)) as u16;
+        println!("SP+{:<2} {:04X}: {:04X}", i * 2, address, high << 8 | low);
+    }
+}
+
+// Split "<spec> if <condition>" and parse the condition
+fn split_condition(text: &str) -> Result<(&str, Option<Condition>), String> {
+    let (spec, condition) = match text.strip_prefix("if ") {
+        Some(condition) => ("", Some(condition)),
+        None => match text.split_once(" if ") {
+            Some((spec, condition)) => (spec, Some(condition)),
+            None => (text, None),
+        },
+    };
+
+    match condition {
+        Some(condition) => {
+            let expr = condition.parse()?;
+            Ok((spec.trim(), Some(Condition { text: condition.trim().to_string(), expr })))
+        }
+        None => Ok((spec.trim(), None)),
     }
+}
+
+// Addresses are always hex, with or without the 0x
+fn parse_hex(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
 
-    // default case, step and do nothing
-    0
-}
\ No newline at end of file
+fn parse_port(text: &str) -> Option<u8> {
+    u8::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+// A single address or start-end
+fn parse_range(text: &str) -> Option<(u16, u16)> {
+    match text.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_hex(start)?, parse_hex(end)?);
+            (start <= end).then_some((start, end))
+        }
+        None => parse_hex(text).map(|address| (address, address)),
+    }
+}
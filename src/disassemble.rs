@@ -9,7 +9,7 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0x05 => String::from("DCR B"),
         0x06 => {opbytes=2;format!("MVI B,#${:02x}",data[0])},
         0x07 => String::from("RLC"),
-        //NO 0x08
+        0x08 => String::from("*NOP"),
         0x09 => String::from("DAD B"),
         0x0A => String::from("LDAX B"),
         0x0B => String::from("DCX B"),
@@ -17,7 +17,7 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0x0D => String::from("DCR C"),
         0x0E => {opbytes=2;format!("MVI C,#${:02x}",data[0])},
         0x0f => String::from("RRC"),
-        //NO 0x10
+        0x10 => String::from("*NOP"),
         0x11 => {opbytes=3;format!("LXI D,#${:02x}{:02x}",data[1],data[0])},
         0x12 => String::from("STAX D"),
         0x13 => String::from("INX D"),
@@ -25,23 +25,23 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0x15 => String::from("DCR D"),
         0x16 => {opbytes=2;format!("MVI D,#${:02x}",data[0])},
         0x17 => String::from("RAL"),
-        //NO 0x18
+        0x18 => String::from("*NOP"),
         0x19 => String::from("DAD D"),
         0x1A => String::from("LDAX D"),
         0x1B => String::from("DCX D"),
         0x1C => String::from("INR E"),
         0x1D => String::from("DCR E"),
         0x1E => {opbytes=2;format!("MVI E,#${:02x}",data[0])},
-        0x1F => format!("RAR"),
-        //NO 0x20
-        0x21 => {opbytes=3;format!("LXI D,#${:02x}{:02x}",data[1],data[0])},
+        0x1F => String::from("RAR"),
+        0x20 => String::from("*NOP"),
+        0x21 => {opbytes=3;format!("LXI H,#${:02x}{:02x}",data[1],data[0])},
         0x22 => {opbytes=3;format!("SHLD #${:02x}{:02x}",data[1],data[0])},
         0x23 => String::from("INX H"),
         0x24 => String::from("INR H"),
         0x25 => String::from("DCR H"),
         0x26 => {opbytes=2;format!("MVI H,#${:02x}",data[0])},
         0x27 => String::from("DAA"),
-        //NO 0x28
+        0x28 => String::from("*NOP"),
         0x29 => String::from("DAD H"),
         0x2A => {opbytes=3;format!("LHLD #${:02x}{:02x}",data[1],data[0])},
         0x2B => String::from("DCX H"),
@@ -49,7 +49,7 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0x2D => String::from("DCR L"),
         0x2E => {opbytes=2;format!("MVI L,#${:02x}",data[0])},
         0x2F => String::from("CMA"),
-        //NO 0x30
+        0x30 => String::from("*NOP"),
         0x31 => {opbytes=3;format!("LXI SP, #${:02x}{:02x}",data[1],data[0])},
         0x32 => {opbytes=3;format!("STA #${:02x}{:02x}",data[1],data[0])},
         0x33 => String::from("INX SP"),
@@ -57,7 +57,7 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0x35 => String::from("DCR M"),
         0x36 => {opbytes=2;format!("MVI M,#${:02x}",data[0])},
         0x37 => String::from("STC"),
-        //NO 0x38
+        0x38 => String::from("*NOP"),
         0x39 => String::from("DAD SP"),
         0x3A => {opbytes=3;format!("LDA #${:02x}{:02x}",data[1],data[0])},
         0x3B => String::from("DCX SP"),
@@ -204,7 +204,7 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0xC8 => String::from("RZ"),
         0xC9 => String::from("RET"),
         0xCA => {opbytes=3;format!("JZ #${:02x}{:02x}",data[1],data[0])},
-        //NO 0xCB
+        0xCB => {opbytes=3;format!("*JMP #${:02x}{:02x}",data[1],data[0])},
         0xCC => {opbytes=3;format!("CZ #${:02x}{:02x}",data[1],data[0])},
         0xCD => {opbytes=3;format!("CALL #${:02x}{:02x}",data[1],data[0])},
         0xCE => {opbytes=2;format!("ACI #${:02x}",data[0])},
@@ -218,11 +218,11 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0xD6 => {opbytes=2;format!("SUI #${:02x}",data[0])},
         0xD7 => String::from("RST 2"),
         0xD8 => String::from("RC"),
-        //NO 0xD9
+        0xD9 => String::from("*RET"),
         0xDA => {opbytes=3;format!("JC #${:02x}{:02x}",data[1],data[0])},
         0xDB => {opbytes=2;format!("IN #${:02x}",data[0])},
         0xDC => {opbytes=3;format!("CC #${:02x}{:02x}",data[1],data[0])},
-        //NO 0xDD
+        0xDD => {opbytes=3;format!("*CALL #${:02x}{:02x}",data[1],data[0])},
         0xDE => {opbytes=2;format!("SBI #${:02x}",data[0])},
         0xDF => String::from("RST 3"),
         0xE0 => String::from("RPO"),
@@ -238,7 +238,7 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0xEA => {opbytes=3;format!("JPE #${:02x}{:02x}",data[1],data[0])},
         0xEB => String::from("XCHG"),
         0xEC => {opbytes=3;format!("CPE #${:02x}{:02x}",data[1],data[0])},
-        //NO 0xED
+        0xED => {opbytes=3;format!("*CALL #${:02x}{:02x}",data[1],data[0])},
         0xEE => {opbytes=2;format!("XRI #${:02x}",data[0])},
         0xEF => String::from("RST 5"),
         0xF0 => String::from("RP"),
@@ -254,11 +254,9 @@ pub fn process_instruction(opcode: u8, data: &[u8]) -> (usize,String) {
         0xFA => {opbytes=3;format!("JM #${:02x}{:02x}",data[1],data[0])},
         0xFB => String::from("EI"),
         0xFC => {opbytes=3;format!("CM #${:02x}{:02x}",data[1],data[0])},
-        //NO 0xFD
+        0xFD => {opbytes=3;format!("*CALL #${:02x}{:02x}",data[1],data[0])},
         0xFE => {opbytes=2;format!("CPI #${:02x}",data[0])},
         0xFF => String::from("RST 7"),
-        // ... more opcodes ...
-        _ => String::from("Unknown"),
     };
-    return (opbytes,operation)
+    (opbytes,operation)
 }
\ No newline at end of file
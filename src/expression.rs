@@ -0,0 +1,424 @@
+use std::str::FromStr;
+
+use crate::cpu::{Bus, Register, RegisterPair, State8080};
+
+// Little expression language for debugger conditions, e.g.
+//   pc == 0x0ADD && a > 3
+//   [hl] != 0 || byte[0x20E7] == 1
+// Numbers are decimal or 0x hex. Names are the registers (a b c d e h l),
+// pairs (bc de hl sp psw), pc and the flags (z s p cy ac). [x] or byte[x]
+// reads a byte of memory, word[x] a little endian word. Operators are C's,
+// comparisons and logic give 1 or 0, and anything non-zero counts as true.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    Z,
+    S,
+    P,
+    CY,
+    AC,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not, // !
+    Invert, // ~
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    // Higher binds tighter, same order as C
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 10,
+            BinaryOp::Add | BinaryOp::Sub => 9,
+            BinaryOp::Shl | BinaryOp::Shr => 8,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 7,
+            BinaryOp::Eq | BinaryOp::Ne => 6,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitOr => 3,
+            BinaryOp::And => 2,
+            BinaryOp::Or => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Reg(Register),
+    Pair(RegisterPair),
+    Pc,
+    Flag(Flag),
+    Byte(Box<Expr>),
+    Word(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval<B: Bus>(&self, state: &State8080<B>) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Reg(reg) => state.reg(*reg) as i64,
+            Expr::Pair(reg) => state.reg_pair(*reg) as i64,
+            Expr::Pc => state.get_pc() as i64,
+            Expr::Flag(flag) => {
+                let flags = state.flags();
+                let set = match flag {
+                    Flag::Z => flags.z,
+                    Flag::S => flags.s,
+                    Flag::P => flags.p,
+                    Flag::CY => flags.cy,
+                    Flag::AC => flags.ac,
+                };
+                set as i64
+            }
+            Expr::Byte(address) => state.peek_mem(address.eval(state) as u16) as i64,
+            Expr::Word(address) => {
+                let address = address.eval(state) as u16;
+                let low = state.peek_mem(address) as i64;
+                let high = state.peek_mem(address.wrapping_add(1)) as i64;
+                high << 8 | low
+            }
+            Expr::Unary(op, operand) => {
+                let value = operand.eval(state);
+                match op {
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::Invert => !value,
+                }
+            }
+            // Short circuit, so [x] on the right only gets read when it matters
+            Expr::Binary(BinaryOp::And, left, right) => (left.eval(state) != 0 && right.eval(state) != 0) as i64,
+            Expr::Binary(BinaryOp::Or, left, right) => (left.eval(state) != 0 || right.eval(state) != 0) as i64,
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(state), right.eval(state));
+                match op {
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    // Dividing by zero gives 0 rather than stopping the emulator
+                    BinaryOp::Div => left.checked_div(right).unwrap_or(0),
+                    BinaryOp::Rem => left.checked_rem(right).unwrap_or(0),
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Shl => left.wrapping_shl(right as u32),
+                    BinaryOp::Shr => left.wrapping_shr(right as u32),
+                    BinaryOp::Lt => (left < right) as i64,
+                    BinaryOp::Le => (left <= right) as i64,
+                    BinaryOp::Gt => (left > right) as i64,
+                    BinaryOp::Ge => (left >= right) as i64,
+                    BinaryOp::Eq => (left == right) as i64,
+                    BinaryOp::Ne => (left != right) as i64,
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+        }
+    }
+
+    // The usual way to use one, as a condition
+    pub fn is_true<B: Bus>(&self, state: &State8080<B>) -> bool {
+        self.eval(state) != 0
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let expr = parser.expression(0)?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?} in {}", token, s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+// Longest first so "<=" isn't read as "<" then "="
+const OPERATORS: [&str; 24] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+    "(", ")", "[", "]",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap_or(' ');
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let word = &rest[..end];
+            let token = if c.is_ascii_digit() {
+                let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                Token::Number(value.map_err(|_| format!("Invalid number: {}", word))?)
+            } else {
+                Token::Name(word.to_lowercase())
+            };
+            tokens.push(token);
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("Unexpected '{}' in {}", c, text))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(found)) if found == op => Ok(()),
+            Some(token) => Err(format!("Expected '{}', got {:?}", op, token)),
+            None => Err(format!("Expected '{}' before the end", op)),
+        }
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        let op = match self.tokens.get(self.position) {
+            Some(Token::Op(op)) => *op,
+            _ => return None,
+        };
+        Some(match op {
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "<<" => BinaryOp::Shl,
+            ">>" => BinaryOp::Shr,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "&" => BinaryOp::BitAnd,
+            "^" => BinaryOp::BitXor,
+            "|" => BinaryOp::BitOr,
+            "&&" => BinaryOp::And,
+            "||" => BinaryOp::Or,
+            _ => return None,
+        })
+    }
+
+    // Precedence climbing, only takes operators that bind tighter than `min`
+    fn expression(&mut self, min: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.binary_op() {
+            if op.precedence() <= min {
+                break;
+            }
+            self.position += 1;
+            let right = self.expression(op.precedence())?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = match self.tokens.get(self.position) {
+            Some(Token::Op("-")) => UnaryOp::Neg,
+            Some(Token::Op("!")) => UnaryOp::Not,
+            Some(Token::Op("~")) => UnaryOp::Invert,
+            _ => return self.primary(),
+        };
+        self.position += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Op("(")) => {
+                let expr = self.expression(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Op("[")) => Ok(Expr::Byte(Box::new(self.deref()?))),
+            Some(Token::Name(name)) => match name.as_str() {
+                "byte" => {
+                    self.expect("[")?;
+                    Ok(Expr::Byte(Box::new(self.deref()?)))
+                }
+                "word" => {
+                    self.expect("[")?;
+                    Ok(Expr::Word(Box::new(self.deref()?)))
+                }
+                "pc" => Ok(Expr::Pc),
+                "z" => Ok(Expr::Flag(Flag::Z)),
+                "s" => Ok(Expr::Flag(Flag::S)),
+                "p" => Ok(Expr::Flag(Flag::P)),
+                "cy" => Ok(Expr::Flag(Flag::CY)),
+                "ac" => Ok(Expr::Flag(Flag::AC)),
+                _ => name
+                    .parse()
+                    .map(Expr::Reg)
+                    .or_else(|_| name.parse().map(Expr::Pair))
+                    .map_err(|_| format!("Unknown name: {}", name)),
+            },
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err(String::from("Expression ends too soon")),
+        }
+    }
+
+    // The inside of [...], the opening bracket already taken
+    fn deref(&mut self) -> Result<Expr, String> {
+        let address = self.expression(0)?;
+        self.expect("]")?;
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::cpm::FlatMemory;
+
+    fn eval(text: &str) -> i64 {
+        let state = State8080::new(FlatMemory::new());
+        text.parse::<Expr>().unwrap().eval(&state)
+    }
+
+    // Counts every look at memory, to see what a short circuit skipped
+    #[derive(Default)]
+    struct CountingBus {
+        reads: Cell<u32>,
+    }
+
+    impl Bus for CountingBus {
+        fn read_byte(&self, _address: u16) -> u8 {
+            self.reads.set(self.reads.get() + 1);
+            1
+        }
+
+        fn write_byte(&mut self, _address: u16, _value: u8) {}
+
+        fn port_in(&mut self, _port: u8) -> u8 {
+            0
+        }
+
+        fn port_out(&mut self, _port: u8, _value: u8) {}
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("1 | 2 == 2"), 1);
+        assert_eq!(eval("0 && 1 || 1"), 1);
+        assert_eq!(eval("-2 * 3"), -6);
+        assert_eq!(
+            "1 + 2 * 3".parse::<Expr>(),
+            Ok(Expr::Binary(
+                BinaryOp::Add,
+                Box::new(Expr::Number(1)),
+                Box::new(Expr::Binary(BinaryOp::Mul, Box::new(Expr::Number(2)), Box::new(Expr::Number(3))))
+            ))
+        );
+    }
+
+    #[test]
+    fn left_associative() {
+        assert_eq!(eval("1-2-3"), -4);
+        assert_eq!(eval("16 / 4 / 2"), 2);
+        assert_eq!(eval("1 << 2 << 3"), 32);
+    }
+
+    #[test]
+    fn memory_reads() {
+        let mut state = State8080::new(FlatMemory::new());
+        state.set_reg_pair(RegisterPair::HL, 0x2000);
+        state.write_mem(0x2000, 0x42);
+        state.write_mem(0x2001, 0x12);
+
+        let eval = |text: &str| text.parse::<Expr>().unwrap().eval(&state);
+        assert_eq!(eval("[hl]"), 0x42);
+        assert_eq!(eval("[hl + 1]"), 0x12);
+        assert_eq!(eval("byte[0x2000]"), 0x42);
+        assert_eq!(eval("word[hl]"), 0x1242);
+        assert_eq!(eval("word[0x2000] == 0x1242 && [hl] != 0"), 1);
+    }
+
+    #[test]
+    fn short_circuit() {
+        let state = State8080::new(CountingBus::default());
+        let reads = |text: &str| {
+            state.bus.reads.set(0);
+            text.parse::<Expr>().unwrap().eval(&state);
+            state.bus.reads.get()
+        };
+        assert_eq!(reads("0 && [0]"), 0);
+        assert_eq!(reads("1 || byte[0]"), 0);
+        assert_eq!(reads("1 && [0]"), 1);
+        assert_eq!(reads("0 || word[0]"), 2);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!("a >".parse::<Expr>(), Err(String::from("Expression ends too soon")));
+        assert!("(((1".parse::<Expr>().is_err());
+        assert!("[hl".parse::<Expr>().is_err());
+        assert_eq!("foo == 1".parse::<Expr>(), Err(String::from("Unknown name: foo")));
+        assert!("0x".parse::<Expr>().is_err());
+        assert!("1 2".parse::<Expr>().is_err());
+        assert!("a @ 1".parse::<Expr>().is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::machine::Inputs;
+
+// Whatever the player is sitting in front of: somewhere to show frames and
+// somewhere for button presses to come from
+pub trait Frontend {
+    // Show a finished SCREEN_WIDTH x SCREEN_HEIGHT ARGB frame
+    fn present(&mut self, frame: &[u32]) -> Result<(), String>;
+
+    // Bring `inputs` up to date, false once the player wants out
+    fn poll_input(&mut self, inputs: &mut Inputs) -> bool;
+
+    // True once each time the player asks to drop into the debugger
+    fn take_break(&mut self) -> bool {
+        false
+    }
+}
+
+// A button going down or up at the start of a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptEvent {
+    pub frame: u64,
+    pub button: String,
+    pub pressed: bool,
+}
+
+// Canned input for runs with nobody at the controls
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<ScriptEvent>,
+}
+
+impl InputScript {
+    // One `<frame> <button> down|up` per line, anything after a # is a comment.
+    // Buttons are the names Inputs::set takes.
+    pub fn parse(text: &str) -> Result<InputScript, String> {
+        let mut events = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (frame, button, action) = match fields[..] {
+                [frame, button, action] => (frame, button, action),
+                _ => return Err(format!("Expected <frame> <button> down|up, got {}", line)),
+            };
+            let frame = frame.parse().map_err(|_| format!("Invalid frame number: {}", frame))?;
+            let pressed = match action {
+                "down" => true,
+                "up" => false,
+                _ => return Err(format!("Expected down or up, got {}", action)),
+            };
+            // Catch typos now rather than halfway through a run
+            Inputs::default().set(button, pressed)?;
+
+            events.push(ScriptEvent {
+                frame,
+                button: button.to_string(),
+                pressed,
+            });
+        }
+
+        // Stable, so presses on the same frame keep their order
+        events.sort_by_key(|event| event.frame);
+        Ok(InputScript { events })
+    }
+}
+
+// No display, input comes from a script. Frames are only counted, whoever
+// wants the picture can get it from the Video that rendered it.
+pub struct Headless {
+    pub frames: u64,
+    script: InputScript,
+    next_event: usize,
+}
+
+impl Headless {
+    pub fn new() -> Headless {
+        Headless::with_script(InputScript::default())
+    }
+
+    pub fn with_script(script: InputScript) -> Headless {
+        Headless {
+            frames: 0,
+            script,
+            next_event: 0,
+        }
+    }
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Headless::new()
+    }
+}
+
+impl Frontend for Headless {
+    fn present(&mut self, _frame: &[u32]) -> Result<(), String> {
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Apply everything scripted up to the frame about to run
+    fn poll_input(&mut self, inputs: &mut Inputs) -> bool {
+        while let Some(event) = self.script.events.get(self.next_event) {
+            if event.frame > self.frames {
+                break;
+            }
+            // Checked when the script was parsed
+            let _ = inputs.set(&event.button, event.pressed);
+            self.next_event += 1;
+        }
+        true
+    }
+}
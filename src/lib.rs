@@ -0,0 +1,16 @@
+// Intel 8080 emulator core. The CPU talks to the outside world through
+// cpu::Bus, machine has the Space Invaders board and cpm a bare bones CP/M
+// environment for the CPU exerciser programs.
+pub mod audio;
+pub mod cpm;
+pub mod cpu;
+pub mod debugger;
+pub mod disassemble;
+pub mod expression;
+pub mod frontend;
+pub mod machine;
+pub mod memory;
+pub mod options;
+pub mod overlay;
+pub mod synth;
+pub mod video;
@@ -0,0 +1,588 @@
+use crate::audio::Mixer;
+use crate::memory::Memory;
+use crate::cpu::{self, Bus, State8080, StepError};
+
+use std::fmt;
+
+pub const CYCLES_PER_FRAME: u64 = 2_000_000 / 60; // 2 MHz CPU, 60 Hz video
+pub const WATCHDOG_DEFAULT_FRAMES: u64 = 255; // about 4 seconds
+
+// The monitor draws 262 lines a frame, 224 of them visible
+pub const SCANLINES: u64 = 262;
+pub const MID_SCREEN_LINE: u64 = 96; // RST 1
+pub const VBLANK_LINE: u64 = 224; // RST 2
+
+// Video RAM holds one scanline per 32 bytes, one bit per pixel
+pub const VRAM_START: u16 = 0x2400;
+pub const VRAM_END: u16 = 0x3FFF;
+pub const BYTES_PER_LINE: usize = 32;
+
+// OUT 3 sound latch bits
+pub const SOUND_UFO: u8 = 0x01; // repeats for as long as the bit is set
+pub const SOUND_SHOT: u8 = 0x02;
+pub const SOUND_PLAYER_DIE: u8 = 0x04;
+pub const SOUND_INVADER_DIE: u8 = 0x08;
+pub const SOUND_EXTENDED_PLAY: u8 = 0x10;
+pub const SOUND_AMP_ENABLE: u8 = 0x20;
+
+// OUT 5 sound latch bits
+pub const SOUND_FLEET_1: u8 = 0x01; // the four notes of the march
+pub const SOUND_FLEET_2: u8 = 0x02;
+pub const SOUND_FLEET_3: u8 = 0x04;
+pub const SOUND_FLEET_4: u8 = 0x08;
+pub const SOUND_UFO_HIT: u8 = 0x10;
+pub const COCKTAIL_FLIP: u8 = 0x20; // screen flip for player 2 on cocktail cabinets
+
+// One player's worth of joystick and button
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Controls {
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+}
+
+impl Controls {
+    // fire/left/right sit in bits 4-6 on every input port
+    fn bits(&self) -> u8 {
+        (self.fire as u8) << 4 | (self.left as u8) << 5 | (self.right as u8) << 6
+    }
+}
+
+// Everything on the cabinet a person can press
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Inputs {
+    pub coin: bool,
+    pub tilt: bool,
+    pub p1_start: bool,
+    pub p2_start: bool,
+    pub p1: Controls,
+    pub p2: Controls,
+}
+
+impl Inputs {
+    // Press or release a button by name, used by input scripts:
+    // coin, tilt, p1_start, p2_start, p1_left, p1_right, p1_fire and the p2 ones
+    pub fn set(&mut self, button: &str, pressed: bool) -> Result<(), String> {
+        let control = match button.trim() {
+            "coin" => &mut self.coin,
+            "tilt" => &mut self.tilt,
+            "p1_start" => &mut self.p1_start,
+            "p2_start" => &mut self.p2_start,
+            "p1_left" => &mut self.p1.left,
+            "p1_right" => &mut self.p1.right,
+            "p1_fire" => &mut self.p1.fire,
+            "p2_left" => &mut self.p2.left,
+            "p2_right" => &mut self.p2.right,
+            "p2_fire" => &mut self.p2.fire,
+            b => return Err(format!("Unknown button: {}", b)),
+        };
+        *control = pressed;
+        Ok(())
+    }
+}
+
+// The operator settings DIP switch bank, read back on IN 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DipSwitches {
+    pub ships: u8, // 3 to 6 ships per game
+    pub bonus_at_1000: bool, // extra ship at 1000 points instead of 1500
+    pub coin_info: bool, // show the coin info on the attract screen
+}
+
+impl Default for DipSwitches {
+    // Factory settings
+    fn default() -> Self {
+        DipSwitches {
+            ships: 3,
+            bonus_at_1000: false,
+            coin_info: true,
+        }
+    }
+}
+
+impl DipSwitches {
+    // Ships in bits 0-1, bonus in bit 3, coin info in bit 7 (set means hidden)
+    pub fn port_bits(&self) -> u8 {
+        (self.ships.clamp(3, 6) - 3)
+            | (self.bonus_at_1000 as u8) << 3
+            | (!self.coin_info as u8) << 7
+    }
+
+    // Change one setting by name, shared by the command line and config files:
+    // ships = 3..6, bonus = 1000|1500, coin_info = on|off
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match (key.trim(), value.trim()) {
+            ("ships", v) => match v.parse::<u8>() {
+                Ok(ships @ 3..=6) => self.ships = ships,
+                _ => return Err(format!("ships must be 3 to 6, got {}", v)),
+            },
+            ("bonus", "1000") => self.bonus_at_1000 = true,
+            ("bonus", "1500") => self.bonus_at_1000 = false,
+            ("bonus", v) => return Err(format!("bonus must be 1000 or 1500, got {}", v)),
+            ("coin_info", "on") => self.coin_info = true,
+            ("coin_info", "off") => self.coin_info = false,
+            ("coin_info", v) => return Err(format!("coin_info must be on or off, got {}", v)),
+            (k, _) => return Err(format!("Unknown DIP switch: {}", k)),
+        }
+        Ok(())
+    }
+
+    // Read `key = value` lines, anything after a # is a comment
+    pub fn from_config(text: &str) -> Result<DipSwitches, String> {
+        let mut dips = DipSwitches::default();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => dips.set(key, value)?,
+                None => return Err(format!("Expected key = value, got {}", line)),
+            }
+        }
+        Ok(dips)
+    }
+}
+
+pub struct Port {
+    pub write2: u8, // shift amount
+    pub shift0: u8,
+    pub shift1: u8,
+    pub inputs: Inputs,
+    pub dip_switches: DipSwitches,
+    pub sound1: u8, // OUT 3 latch
+    pub sound2: u8, // OUT 5 latch
+    pub watchdog_kicked: bool, // OUT 6 happened since someone last looked
+}
+
+impl Port {
+    pub fn new() -> Port {
+        Port {
+            write2: 0,
+            shift0: 0,
+            shift1: 0,
+            inputs: Inputs::default(),
+            dip_switches: DipSwitches::default(),
+            sound1: 0,
+            sound2: 0,
+            watchdog_kicked: false,
+        }
+    }
+}
+
+impl Default for Port {
+    fn default() -> Self {
+        Port::new()
+    }
+}
+
+// The Midway Space Invaders board: ROM/RAM/VRAM with mirroring and the
+// external shift register hanging off the I/O ports
+pub struct SpaceInvaders {
+    memory: Memory,
+    pub port: Port,
+    pub watchdog: Watchdog,
+    pub beam: Beam,
+}
+
+impl SpaceInvaders {
+    pub fn new() -> SpaceInvaders {
+        SpaceInvaders {
+            memory: Memory::new(),
+            port: Port::new(),
+            watchdog: Watchdog::new(WATCHDOG_DEFAULT_FRAMES * CYCLES_PER_FRAME),
+            beam: Beam::new(),
+        }
+    }
+
+    // special way for us to write our file to ROM
+    pub fn write_rom_mem(&mut self, address: u16, value: u8) {
+        self.memory.rom_write_byte(address, value)
+    }
+
+    pub fn read_mem_chunk(&self, start_address: u16, end_address: u16) -> &[u8] {
+        self.memory.read_byte_chunk(start_address, end_address)
+    }
+}
+
+impl Default for SpaceInvaders {
+    fn default() -> Self {
+        SpaceInvaders::new()
+    }
+}
+
+impl Bus for SpaceInvaders {
+    fn read_byte(&self, address: u16) -> u8 {
+        self.memory.read_byte(address)
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.memory.peek_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.memory.write_byte(address, value)
+    }
+
+    fn port_in(&mut self, port: u8) -> u8 {
+        let inputs = &self.port.inputs;
+        match port {
+            // bits 1-3 are tied high, the rest mirror player 1
+            0 => 0x0E | inputs.p1.bits(),
+            1 => {
+                inputs.coin as u8
+                    | (inputs.p2_start as u8) << 1
+                    | (inputs.p1_start as u8) << 2
+                    | 0x08 // always set
+                    | inputs.p1.bits()
+            }
+            2 => self.port.dip_switches.port_bits() | (inputs.tilt as u8) << 2 | inputs.p2.bits(),
+            3 => {
+                let v: u16 = ((self.port.shift1 as u16) << 8) | (self.port.shift0 as u16);
+                ((v >> (8 - self.port.write2)) & 0xFF) as u8
+            }
+            _ => 0,
+        }
+    }
+
+    fn port_out(&mut self, port: u8, value: u8) {
+        match port {
+            2 => {
+                self.port.write2 = value & 0x7;
+            }
+            3 => {
+                self.port.sound1 = value;
+            }
+            4 => {
+                self.port.shift0 = self.port.shift1;
+                self.port.shift1 = value;
+            }
+            5 => {
+                self.port.sound2 = value;
+            }
+            6 => {
+                self.port.watchdog_kicked = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn take_fault(&mut self) -> Option<u16> {
+        self.memory.take_fault()
+    }
+}
+
+// Counts CPU cycles since the game last wrote to port 6, a timeout of 0
+// turns it off
+pub struct Watchdog {
+    timeout: u64,
+    elapsed: u64,
+}
+
+impl Watchdog {
+    pub fn new(timeout_cycles: u64) -> Watchdog {
+        Watchdog {
+            timeout: timeout_cycles,
+            elapsed: 0,
+        }
+    }
+
+    pub fn kick(&mut self) {
+        self.elapsed = 0;
+    }
+
+    // Returns true once the timeout runs out, and starts counting again
+    pub fn tick(&mut self, cycles: u64) -> bool {
+        if self.timeout == 0 {
+            return false;
+        }
+
+        self.elapsed += cycles;
+        if self.elapsed >= self.timeout {
+            self.elapsed = 0;
+            return true;
+        }
+        false
+    }
+}
+
+// Where the video hardware is drawing, counted in CPU cycles since the top
+// of the frame. The board raises its two interrupts off this.
+pub struct Beam {
+    cycle: u64,
+    frames: u64,
+    screen: Vec<u8>, // VRAM as each line was when the beam got to it
+}
+
+impl Beam {
+    pub fn new() -> Beam {
+        Beam {
+            cycle: 0,
+            frames: 0,
+            screen: vec![0; VBLANK_LINE as usize * BYTES_PER_LINE],
+        }
+    }
+
+    pub fn line(&self) -> u64 {
+        self.cycle * SCANLINES / CYCLES_PER_FRAME
+    }
+
+    // Frames finished so far, counted at vblank
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    // The picture as the monitor shows it, laid out like VRAM. Complete once
+    // the beam reaches vblank, mid frame the lines below it are still the
+    // previous frame's.
+    pub fn screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    // Move the beam on, picking up the lines it enters from `vram`. Returns
+    // the RST number if it just reached one of the interrupt lines. An
+    // instruction is far shorter than a scanline so it can't pass both at once.
+    pub fn tick(&mut self, cycles: u64, vram: &[u8]) -> Option<u8> {
+        let before = self.line();
+        self.cycle = (self.cycle + cycles) % CYCLES_PER_FRAME;
+        let after = self.line();
+        let crossed = |line: u64| before < line && after >= line;
+
+        let mut line = before;
+        while line != after {
+            line = (line + 1) % SCANLINES;
+            let start = line as usize * BYTES_PER_LINE;
+            if line < VBLANK_LINE {
+                if let Some(row) = vram.get(start..start + BYTES_PER_LINE) {
+                    self.screen[start..start + BYTES_PER_LINE].copy_from_slice(row);
+                }
+            }
+        }
+
+        if crossed(MID_SCREEN_LINE) {
+            Some(1)
+        } else if crossed(VBLANK_LINE) {
+            self.frames += 1;
+            Some(2)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Beam {
+    fn default() -> Self {
+        Beam::new()
+    }
+}
+
+// Things the board did on its own that the frontend may want to know about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    // The game stopped kicking the watchdog, pc is where it was stuck
+    WatchdogReset { pc: u16 },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::WatchdogReset { pc } => write!(f, "watchdog reset the CPU at {:04X}", pc),
+        }
+    }
+}
+
+// Advance the board hardware by the cycles the CPU just spent
+pub fn tick(state: &mut State8080<SpaceInvaders>, cycles: u8) -> Option<Event> {
+    if std::mem::take(&mut state.bus.port.watchdog_kicked) {
+        state.bus.watchdog.kick();
+    }
+
+    // The CPU takes these once it has interrupts enabled
+    let bus = &mut state.bus;
+    let vram = bus.memory.read_byte_chunk(VRAM_START, VRAM_END);
+    if let Some(rst) = bus.beam.tick(cycles as u64, vram) {
+        cpu::generate_interrupt(state, rst);
+    }
+
+    if state.bus.watchdog.tick(cycles as u64) {
+        let pc = state.get_pc();
+        state.reset();
+        return Some(Event::WatchdogReset { pc });
+    }
+
+    None
+}
+
+// One instruction with the rest of the board kept in step: the CPU, then the
+// board hardware for the cycles it took (even if it raised an error), then
+// the sound. Anything the board did along the way goes to `on_event`.
+pub fn step<F>(state: &mut State8080<SpaceInvaders>, mixer: Option<&mut Mixer>, mut on_event: F) -> Result<u8, StepError>
+where
+    F: FnMut(Event),
+{
+    let result = cpu::step(state);
+    let cycles = match &result {
+        Ok(cycles) => *cycles,
+        Err(err) => err.cycles,
+    };
+
+    if let Some(event) = tick(state, cycles) {
+        on_event(event);
+    }
+    if let Some(mixer) = mixer {
+        mixer.clock(cycles, state.bus.port.sound1, state.bus.port.sound2);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_port_bits() {
+        let mut board = SpaceInvaders::new();
+        // Nothing pressed, just the bits that are tied high
+        assert_eq!((board.port_in(0), board.port_in(1), board.port_in(2)), (0x0E, 0x08, 0x00));
+
+        board.port.inputs = Inputs {
+            coin: true,
+            p1_start: true,
+            p1: Controls { left: false, right: true, fire: true },
+            ..Inputs::default()
+        };
+        assert_eq!(board.port_in(0), 0x0E | 0x10 | 0x40);
+        assert_eq!(board.port_in(1), 0x01 | 0x04 | 0x08 | 0x10 | 0x40);
+        assert_eq!(board.port_in(2), 0x00);
+
+        board.port.inputs = Inputs {
+            tilt: true,
+            p2_start: true,
+            p2: Controls { left: true, right: false, fire: false },
+            ..Inputs::default()
+        };
+        board.port.dip_switches = DipSwitches { ships: 6, bonus_at_1000: true, coin_info: false };
+        assert_eq!(board.port_in(1), 0x02 | 0x08);
+        assert_eq!(board.port_in(2), 0x8B | 0x04 | 0x20);
+    }
+
+    #[test]
+    fn shift_register() {
+        let mut board = SpaceInvaders::new();
+        board.port_out(4, 0xAB);
+        board.port_out(4, 0xCD);
+        board.port_out(2, 0);
+        assert_eq!(board.port_in(3), 0xCD);
+        board.port_out(2, 4);
+        assert_eq!(board.port_in(3), 0xDA);
+    }
+
+    #[test]
+    fn dip_switch_bits() {
+        // Factory settings are all zeros
+        assert_eq!(DipSwitches::default().port_bits(), 0x00);
+        let dips = DipSwitches { ships: 5, bonus_at_1000: true, coin_info: false };
+        assert_eq!(dips.port_bits(), 0x02 | 0x08 | 0x80);
+    }
+
+    #[test]
+    fn dip_switch_config() {
+        let dips = DipSwitches::from_config("# cabinet.cfg\nships = 5\n\nbonus = 1000 # extra ship early\ncoin_info = off\n").unwrap();
+        assert_eq!(dips, DipSwitches { ships: 5, bonus_at_1000: true, coin_info: false });
+
+        // Anything left out keeps the factory setting
+        assert_eq!(DipSwitches::from_config("ships=4").unwrap(), DipSwitches { ships: 4, ..DipSwitches::default() });
+    }
+
+    #[test]
+    fn dip_switch_errors() {
+        let mut dips = DipSwitches::default();
+        for (key, value) in [("ships", "2"), ("ships", "7"), ("ships", "lots"), ("bonus", "2000"), ("coin_info", "yes"), ("lives", "3")] {
+            assert!(dips.set(key, value).is_err(), "{} = {}", key, value);
+        }
+        // A bad value leaves the switches alone
+        assert_eq!(dips, DipSwitches::default());
+
+        assert!(DipSwitches::from_config("ships 5").is_err());
+        assert!(DipSwitches::from_config("ships = 9").is_err());
+    }
+
+    #[test]
+    fn watchdog_times_out() {
+        let mut watchdog = Watchdog::new(100);
+        assert!(!watchdog.tick(60));
+        assert!(watchdog.tick(40));
+        // and starts over afterwards
+        assert!(!watchdog.tick(99));
+        assert!(watchdog.tick(1));
+
+        let mut watchdog = Watchdog::new(0);
+        assert!(!watchdog.tick(u32::MAX as u64));
+    }
+
+    #[test]
+    fn watchdog_resets_the_cpu_unless_kicked() {
+        let mut state = State8080::new(SpaceInvaders::new());
+        state.bus.watchdog = Watchdog::new(100);
+        state.set_pc(0x1234);
+
+        assert_eq!(tick(&mut state, 60), None);
+        // OUT 6 starts the count again
+        state.bus.port_out(6, 0);
+        assert_eq!(tick(&mut state, 60), None);
+        assert_eq!(state.get_pc(), 0x1234);
+
+        assert_eq!(tick(&mut state, 40), Some(Event::WatchdogReset { pc: 0x1234 }));
+        assert_eq!(state.get_pc(), 0x0000);
+    }
+
+    #[test]
+    fn beam_interrupts() {
+        let mut beam = Beam::new();
+        let mut interrupts = vec![];
+        for _ in 0..CYCLES_PER_FRAME * 2 {
+            if let Some(rst) = beam.tick(1, &[]) {
+                interrupts.push((rst, beam.line()));
+            }
+        }
+        // RST 1 as the beam reaches line 96, RST 2 at 224, once each a frame
+        assert_eq!(interrupts, [(1, 96), (2, 224), (1, 96), (2, 224)]);
+        assert_eq!(beam.frames(), 2);
+        assert_eq!(CYCLES_PER_FRAME, 33333);
+    }
+
+    #[test]
+    fn beam_raises_interrupts_on_the_cpu() {
+        let mut state = State8080::new(SpaceInvaders::new());
+        state.bus.watchdog = Watchdog::new(0);
+        let mut cycles = 0;
+        while !state.interrupt_pending() {
+            tick(&mut state, 4);
+            cycles += 4;
+        }
+        // Raised by the tick that took the beam onto line 96
+        assert_eq!(state.bus.beam.line(), MID_SCREEN_LINE);
+        assert_eq!((cycles - 4) * SCANLINES / CYCLES_PER_FRAME, MID_SCREEN_LINE - 1);
+    }
+
+    #[test]
+    fn beam_picks_up_lines_as_it_passes() {
+        let mut beam = Beam::new();
+        let mut vram = vec![0; VBLANK_LINE as usize * BYTES_PER_LINE];
+        vram[10 * BYTES_PER_LINE] = 0xFF;
+        vram[200 * BYTES_PER_LINE] = 0xFF;
+
+        // Down to line 100, then the game changes both lines
+        while beam.line() < 100 {
+            beam.tick(4, &vram);
+        }
+        vram[10 * BYTES_PER_LINE] = 0x00;
+        vram[200 * BYTES_PER_LINE] = 0x0F;
+        while beam.tick(4, &vram) != Some(2) {}
+
+        // Line 10 was drawn before the change, line 200 after it
+        assert_eq!(beam.screen()[10 * BYTES_PER_LINE], 0xFF);
+        assert_eq!(beam.screen()[200 * BYTES_PER_LINE], 0x0F);
+    }
+}
@@ -1,27 +1,24 @@
-mod disassemble;
-mod memory;
-mod state8080;
-mod debugger;
-
 use std::fs::File;
 use std::io::{Read,Write};
 use std::time::{Instant, Duration};
-use std::thread::{self, Thread};
-
-use disassemble::process_instruction;
 
-use debugger::parse_command;
+use emu_8080::{cpm, cpu, machine};
+use emu_8080::audio::{AudioSink, Mixer};
+use emu_8080::disassemble::process_instruction;
+use emu_8080::frontend::Frontend;
+use emu_8080::options::{MachineOptions, MACHINE_USAGE};
+use emu_8080::video::{Video, SCREEN_HEIGHT, SCREEN_WIDTH};
 
 use minifb::{Window, WindowOptions, Key};
 
 use queues::*;
 
-use crate::state8080::State8080;
+use emu_8080::machine::{Inputs, SpaceInvaders};
+use emu_8080::cpu::{ErrorPolicy, State8080};
+use emu_8080::debugger::{Action, Debugger};
 
-const WIDTH: usize = 256;
-const HEIGHT: usize = 224; //224
 const FRAME_TIME: Duration = Duration::from_nanos(16666667); // 60 Hz frame time
-const FRAME_TIME120: Duration = Duration::from_nanos(8333333); // 120 Hz frame time
+#[allow(dead_code)]
 const DEBUG: bool = false;
 
 // This will read in a hexdump file, parse each opcode, and write out to
@@ -75,7 +72,7 @@ fn parse_file(infile: &'static str, outfile: &'static str) {
                 let _ = outfile.write_all(operation.as_bytes());
                 let _ = outfile.write_all(b"\n");
 
-                i=i+opbytes; // Skip any bytes we've used as direct data
+                i+=opbytes; // Skip any bytes we've used as direct data
             }
 
             let _ = outfile.flush();
@@ -87,7 +84,7 @@ fn parse_file(infile: &'static str, outfile: &'static str) {
 }
 
 // Read the file into a byte vector
-fn read_file(infile: &'static str) -> Result<Vec<u8>, std::io::Error> {
+fn read_file(infile: &str) -> Result<Vec<u8>, std::io::Error> {
     // Open file 'infile' for reading
     let mut file = File::open(infile)?;
 
@@ -100,35 +97,92 @@ fn read_file(infile: &'static str) -> Result<Vec<u8>, std::io::Error> {
     Ok(buffer)
 }
 
+#[allow(dead_code)]
 fn dump_bytes_to_file(bytes: &[u8], file_path: &str) {
     let mut file = File::create(file_path).expect("Failed to create file.");
     file.write_all(bytes).expect("Failed to write file");
 }
+// 8080EXM, the slowest of the exercisers, needs about 24 billion
+const CPM_MAX_CYCLES: u64 = 100_000_000_000;
+
+// Run a CP/M .COM program (cpudiag, 8080EXM etc.) and echo its console output
+fn run_cpm(infile: &str) {
+    let program = match read_file(infile) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            println!("Error reading file: {}", err);
+            return;
+        }
+    };
+
+    let result = cpm::run_com(&program, CPM_MAX_CYCLES, |c| {
+        print!("{}", c);
+        let _ = std::io::stdout().flush();
+    });
+    println!();
+    match result {
+        Ok(cycles) => println!("Program exited after {} cycles", cycles),
+        Err(err) => println!("{}", err),
+    }
+}
+
+// Everything that can be set from the command line
+#[derive(Default)]
+struct Options {
+    cpm: Option<String>, // run this .COM instead of the game
+    machine: MachineOptions,
+    debug: bool, // start in the debugger instead of running
+}
+
+fn usage() -> String {
+    format!("Usage: emu-8080 [--debug] [--cpm <file.com>] {}", MACHINE_USAGE)
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing argument for '{}'", arg));
+        if options.machine.parse_arg(&arg, &mut value)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--debug" => options.debug = true,
+            "--cpm" => options.cpm = Some(value()?),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
 fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            println!("{}", usage());
+            return;
+        }
+    };
+
+    // `--cpm <file.com>` runs a CPU exerciser instead of the game
+    if let Some(program) = &options.cpm {
+        run_cpm(program);
+        return;
+    }
 
     //profiling code
     // Variables for measuring elapsed time
     let mut start_time = Instant::now();
     let mut instruction_count = 0;
 
-    // Attempt at some timing code for emulator precision
-    let target_frequency: f64 = 2000000.0; // 2 MHz target frequency
-    let cycles_per_frame: f64 = target_frequency / 60.0; // Assuming 60 frames per second
-
     // Create a window
-    let mut window = Window::new(
-        "Simple Graphics Example",
-        //swapped em
-        HEIGHT,
-        WIDTH,
-        WindowOptions::default(),
-    )
-    .unwrap_or_else(|e| {
+    let mut frontend = MinifbFrontend::new().unwrap_or_else(|e| {
         panic!("{}", e);
     });
 
-    let mut swapInterrupt = false;
-
     parse_file("invaders", "invaders.8080"); // for disassembly
 
     // for actual emulation
@@ -144,75 +198,119 @@ fn main() {
 
     // first TODO is going to be writing the space invaders game
     // to memory
-    let mut emu8080 = state8080::State8080::default();
+    let mut emu8080 = options.machine.machine();
 
     for (address, byte) in bytes.iter().enumerate() {
-        emu8080.write_rom_mem(address as u16, *byte);
+        emu8080.bus.write_rom_mem(address as u16, *byte);
     }
 
-    let mut last_frame_time = Instant::now();
-    let mut lastInterrupt = Instant::now(); // kick off a timer for interrupts
-    let mut intCounter = Duration::default();
+    // Sound only goes to a .wav for now, there's no live output
+    let mut audio = match options.machine.audio() {
+        Ok(audio) => audio,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    let mut video = Video::new(options.machine.overlay());
 
-    println!("Starting debug loop, enter 'help' to display debug commands.");
+    println!("Press F12 to break into the debugger.");
     //let mut last_instructions: Vec<String> = vec![];
     let mut q: Queue<String> = queue![];
-    let mut total_cycles=0;
+    let mut debugger = Debugger::new();
+    let mut paused = options.debug;
 
-    loop {
-        let mut ret_code=0;
-        let frame_start_time = Instant::now();
+    'running: while frontend.poll_input(&mut emu8080.bus.port.inputs) {
+        if frontend.take_break() {
+            paused = true;
+        }
 
-        
-        last_frame_time = Instant::now();
+        let mut frame_start_time = Instant::now();
 
         // EMULATION BLOCK
-        // Emulate instructions for the current frame
+        // Emulate instructions until the beam reaches vblank
         let mut cycles_executed: f64 = 0.0;
-        while cycles_executed < cycles_per_frame {
-            
+        let frame = emu8080.bus.beam.frames();
+        while emu8080.bus.beam.frames() == frame {
+            if paused {
+                paused = false;
+                if !debug_session(&mut debugger, &mut emu8080, audio.as_mut().map(|(mixer, _)| mixer)) {
+                    break 'running;
+                }
+
+                // Don't count the time sat in the debugger
+                frame_start_time = Instant::now();
+                start_time = Instant::now();
+                instruction_count = 0;
+
+                // The debugger may have run us into the next frame already
+                if emu8080.bus.beam.frames() != frame {
+                    break;
+                }
+            }
+
             // Emulate an instruction
-            let (_, mut diss) = disassemble::process_instruction(emu8080.read_mem(emu8080.get_pc()), emu8080.read_mem_chunk(emu8080.get_pc()+1, emu8080.get_pc()+2));
-            diss = format!("{:04X}: {}, Frame cycles thus far: {}",emu8080.get_pc(),diss,cycles_executed);
-            q.add(diss);
+            let pc = emu8080.get_pc();
+            let (_, mut diss) = process_instruction(emu8080.peek_mem(pc), &[emu8080.peek_mem(pc.wrapping_add(1)), emu8080.peek_mem(pc.wrapping_add(2))]);
+            diss = format!("{:04X}: {}, Frame cycles thus far: {}",pc,diss,cycles_executed);
+            let _ = q.add(diss);
             if q.size() > 1000 {
-                q.remove();
+                let _ = q.remove();
+            }
+            let mixer = audio.as_mut().map(|(mixer, _)| mixer);
+            let a = match machine::step(&mut emu8080, mixer, |event| println!("{}", event)) {
+                Ok(cycles) => cycles as f64,
+                Err(err) => {
+                    println!("CPU error: {}", err);
+                    // Under Halt the CPU has stopped and we quit below
+                    if options.machine.error_policy == ErrorPolicy::Trap {
+                        paused = true;
+                    }
+                    err.cycles as f64
+                }
+            };
+
+            if debugger.is_active() {
+                if let Some(reason) = debugger.check(&emu8080) {
+                    println!("{}", reason);
+                    paused = true;
+                }
+            }
+
+            // Halted with interrupts off means nothing can ever wake it up
+            if emu8080.is_halted() && !emu8080.interrupt_enabled() {
+                println!("CPU halted with interrupts disabled");
+                cpu::print_state(&emu8080);
+                break 'running;
             }
-            let a = state8080::emulate_8080_op(&mut emu8080) as f64;
             instruction_count+=a as i32;
             cycles_executed += a;
-            total_cycles+=a as i32;
 
             // if emu8080.get_pc()==0x09EE {
             //     break;
             // }
-            let elapsed = lastInterrupt.elapsed();
-            // Accumulate the frame time
-            intCounter = elapsed;
-            // Check timers and handle their interrupts if necessary
-            if emu8080.interrupt_enabled() && total_cycles > 16667{
-                lastInterrupt = Instant::now();
-                //intCounter -= FRAME_TIME120;
-                total_cycles=0;
-
-                if swapInterrupt {
-                    state8080::generate_interrupt(&mut emu8080, 2);
-                } else {
-                    state8080::generate_interrupt(&mut emu8080, 1);
-                }
-
-                swapInterrupt = !swapInterrupt;
-            }
         }
         // if emu8080.get_pc()==0x09EE {
         //     break;
         // }
 
-        draw_screen(&mut emu8080, &mut window);
+        // What the beam drew this frame rather than VRAM as it is now, the game
+        // redraws each half of the screen while the beam is in the other one
+        let frame = video.render(emu8080.bus.beam.screen());
+        if let Err(err) = frontend.present(frame) {
+            println!("Error drawing frame: {}", err);
+        }
+
+        if let Some((mixer, sink)) = audio.as_mut() {
+            if let Err(err) = mixer.drain_to(sink) {
+                println!("Error writing audio: {}", err);
+                audio = None;
+            }
+        }
 
         // Sleep to maintain the target frequency
-        let target_frame_time = Duration::from_secs_f64(1.0 / 60.0);//Duration::from_secs_f64(1.0 / target_frequency);
-        let target_time = frame_start_time + target_frame_time;
+        let target_time = frame_start_time + FRAME_TIME;
         loop {
             if Instant::now() >= target_time {
                 break;
@@ -234,143 +332,92 @@ fn main() {
                   
     }
 
+    if let Some((_, mut sink)) = audio {
+        if let Err(err) = sink.finish() {
+            println!("Error writing audio: {}", err);
+        }
+    }
+
     let mut file = File::create("instruction_dump_last1000.txt").unwrap();
 
-    for i in 0..1000  {
-        let string = q.remove().unwrap();
+    while let Ok(string) = q.remove() {
         file.write_all(string.as_bytes()).unwrap();
         file.write_all(b"\n").unwrap();
     }
 }
 
 
-fn draw_screen(state: &mut State8080, window: &mut Window) {
-    let vram_chunk=state.read_mem_chunk(0x2400, 0x3FFF);
-    let mut buffidx = 0;
-
-    // Create a buffer to store the pixel data
-    let mut buffer: Vec<u32> = vec![0; WIDTH * HEIGHT];
-    
-    // First loop for the first half of vram_chunk
-    for v in vram_chunk {
-        for i in 0..8 {
-            let bit = 0b00000001 << i;
-            buffer[buffidx] = if bit & v != 0 { 0xFFFFFFFF } else { 0xFF000000 };
-            buffidx += 1;
+// Sit at the debugger prompt until told to carry on, false means quit
+fn debug_session(debugger: &mut Debugger, state: &mut State8080<SpaceInvaders>, mut mixer: Option<&mut Mixer>) -> bool {
+    println!("Stopped at {:04X}, enter 'help' to display debug commands.", state.get_pc());
+    loop {
+        match debugger.parse_command(state, |state| machine::step(state, mixer.as_deref_mut(), |event| println!("{}", event))) {
+            Action::Stay => {}
+            Action::Resume => return true,
+            Action::Quit => return false,
         }
     }
+}
 
-    // Interpret the input 1D buffer as a 2D grid (a vector of vectors).
-    // The chunks_exact() method splits our buffer into chunks of "WIDTH"-length, effectively giving us the rows of our grid.
-    // .map(|chunk| chunk.to_vec()) simply converts each chunk (or row) into a vector.
-    // .collect() finally gathers these vectors of rows into a single vector.
-    let buffer_grid: Vec<Vec<u32>> = buffer
-    .chunks_exact(WIDTH)
-    .map(|chunk| chunk.to_vec())
-    .collect();
-
-    // Rotate the 2D grid counterclockwise.
-    // (0..WIDTH).rev() generates a reversed range of column indices.
-    // The .flat_map call then takes each reversed column index and goes through each row at that column index.
-    // This iterates bottom to top, so we're effectively taking columns from the right of the original image and appending them to the new image, achieving a 90 degree counterclockwise rotation.
-    // .collect() then gathers these into a single flat vector.
-    let rotated_grid: Vec<u32> = (0..WIDTH).rev()
-    .flat_map(|x| buffer_grid.iter().map(move |row| row[x]))
-    .collect();
-
-    //cleaner to put this here I guess
-
-    // Update the window with the buffer contents
-    //width and height had to be swapped, this cause so many issues. I hate 1D bitmaps <3
-    window.update_with_buffer(&rotated_grid, HEIGHT,WIDTH ).unwrap();
-
-    window.get_keys_pressed(minifb::KeyRepeat::No).iter().for_each(|key| match key {
-        Key::C => {
-            let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-            *coin_counter |= 1; // Set bit 0
-        }, // do whatever io port thing
-        _ => (),
-    });
+// The desktop frontend, a minifb window with the keyboard as the controls
+struct MinifbFrontend {
+    window: Window,
+    break_pressed: bool,
+}
 
-    window.get_keys_released().iter().for_each(|key|
-        match key {
-            Key::C => { 
-                let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-                *coin_counter &= !1; // Clear bit 0
-            }
-            _ => (),
-        }
-    );
+impl MinifbFrontend {
+    fn new() -> Result<MinifbFrontend, String> {
+        let window = Window::new(
+            "Simple Graphics Example",
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            WindowOptions::default(),
+        )
+        .map_err(|err| err.to_string())?;
+        Ok(MinifbFrontend { window, break_pressed: false })
+    }
+}
 
-    window.get_keys_pressed(minifb::KeyRepeat::No).iter().for_each(|key| match key {
-        Key::Enter => {
-            let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-            *coin_counter |= 4; // Set bit 0
-        }, // do whatever io port thing
-        _ => (),
-    });
+impl Frontend for MinifbFrontend {
+    fn present(&mut self, frame: &[u32]) -> Result<(), String> {
+        self.window
+            .update_with_buffer(frame, SCREEN_WIDTH, SCREEN_HEIGHT)
+            .map_err(|err| err.to_string())
+    }
 
-    window.get_keys_released().iter().for_each(|key|
-        match key {
-            Key::Enter => { 
-                let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-                *coin_counter &= !4; // Clear bit 0
+    fn poll_input(&mut self, inputs: &mut Inputs) -> bool {
+        for key in self.window.get_keys_pressed(minifb::KeyRepeat::No) {
+            if key == Key::F12 {
+                self.break_pressed = true;
             }
-            _ => (),
+            set_input(inputs, key, true);
         }
-    );
-
-    window.get_keys_pressed(minifb::KeyRepeat::No).iter().for_each(|key| match key {
-        Key::A => {
-            let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-            *coin_counter |= 0x20; // Set bit 0
-        }, // do whatever io port thing
-        _ => (),
-    });
 
-    window.get_keys_released().iter().for_each(|key|
-        match key {
-            Key::A => { 
-                let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-                *coin_counter &= !0x20; // Clear bit 0
-            }
-            _ => (),
+        for key in self.window.get_keys_released() {
+            set_input(inputs, key, false);
         }
-    );
 
-    window.get_keys_pressed(minifb::KeyRepeat::No).iter().for_each(|key| match key {
-        Key::D => {
-            let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-            *coin_counter |= 0x40; // Set bit 0
-        }, // do whatever io port thing
-        _ => (),
-    });
+        self.window.is_open()
+    }
 
-    window.get_keys_released().iter().for_each(|key|
-        match key {
-            Key::D => { 
-                let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-                *coin_counter &= !0x40; // Clear bit 0
-            }
-            _ => (),
-        }
-    );
+    fn take_break(&mut self) -> bool {
+        std::mem::take(&mut self.break_pressed)
+    }
+}
 
-    window.get_keys_pressed(minifb::KeyRepeat::No).iter().for_each(|key| match key {
-        Key::Space => {
-            let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-            *coin_counter |= 0x10; // Set bit 0
-        }, // do whatever io port thing
+// Map the keyboard onto the cabinet, `pressed` is false on release
+fn set_input(inputs: &mut Inputs, key: Key, pressed: bool) {
+    match key {
+        Key::C => inputs.coin = pressed,
+        Key::T => inputs.tilt = pressed,
+        Key::Enter | Key::Key1 => inputs.p1_start = pressed,
+        Key::Key2 => inputs.p2_start = pressed,
+        Key::A => inputs.p1.left = pressed,
+        Key::D => inputs.p1.right = pressed,
+        Key::Space => inputs.p1.fire = pressed,
+        Key::Left => inputs.p2.left = pressed,
+        Key::Right => inputs.p2.right = pressed,
+        Key::Up => inputs.p2.fire = pressed,
         _ => (),
-    });
-
-    window.get_keys_released().iter().for_each(|key|
-        match key {
-            Key::Space => { 
-                let coin_counter = state.port.io_ports.entry(1).or_insert(0);
-                *coin_counter &= !0x10; // Clear bit 0
-            }
-            _ => (),
-        }
-    );
-}
\ No newline at end of file
+    }
+}
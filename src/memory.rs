@@ -1,8 +1,11 @@
+use std::cell::Cell;
+
 const MEMORY_SIZE: usize = 0x4000; // Total memory size 0x0000-0x3fff main memory
                                    // 0x4000- RAM mirror
 
 pub struct Memory {
     memory: [u8; MEMORY_SIZE],
+    fault: Cell<Option<u16>>, // last address that fell outside the map
 }
 
 fn mirror_address(address: u16) -> u16 {
@@ -20,30 +23,59 @@ impl Memory {
     pub fn new() -> Memory {
         Memory {
             memory: [0; MEMORY_SIZE],
+            fault: Cell::new(None),
         }
     }
 
     pub fn read_byte(&self, address: u16) -> u8 {
         let mirrored_address = mirror_address(address);
-        self.memory[mirrored_address as usize]
+        match self.memory.get(mirrored_address as usize) {
+            Some(value) => *value,
+            None => {
+                // nothing there, the data bus floats
+                self.fault.set(Some(address));
+                0
+            }
+        }
     }
 
+    // Same as read_byte, but looking doesn't count as a fault
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        self.memory.get(mirror_address(address) as usize).copied().unwrap_or(0)
+    }
+
+    // Clamped to the end of memory, so asking for too much just gets less
     pub fn read_byte_chunk(&self, start_address: u16, end_address: u16) -> &[u8] {
-        &self.memory[start_address as usize..=end_address as usize]
+        let end = (end_address as usize).min(MEMORY_SIZE - 1);
+        self.memory.get(start_address as usize..=end).unwrap_or(&[])
     }
 
     // special way for us to write our file to ROM
     pub fn rom_write_byte(&mut self, address: u16, value: u8) {
-        self.memory[address as usize] = value;
+        if let Some(byte) = self.memory.get_mut(address as usize) {
+            *byte = value;
+        }
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
         let mirrored_address = mirror_address(address);
         //no write to ROM
         if address > 0x1FFF{
-            self.memory[mirrored_address as usize] = value; 
+            match self.memory.get_mut(mirrored_address as usize) {
+                Some(byte) => *byte = value,
+                None => self.fault.set(Some(address)),
+            }
         }
     }
 
-    
-}
\ No newline at end of file
+    // Take the last out of range address, if there was one
+    pub fn take_fault(&self) -> Option<u16> {
+        self.fault.take()
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory::new()
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use crate::audio::{self, Mixer, WavSink};
+use crate::cpu::{ErrorPolicy, State8080};
+use crate::machine::{self, DipSwitches, SpaceInvaders, Watchdog};
+use crate::overlay::{Band, Overlay, Profile};
+
+// Command line options for the board, the picture and the sound, shared by
+// the windowed and headless builds so they take the same flags
+#[derive(Debug, Default, Clone)]
+pub struct MachineOptions {
+    pub dip_switches: DipSwitches,
+    pub watchdog_cycles: Option<u64>, // None keeps the board default, 0 turns it off
+    pub error_policy: ErrorPolicy,
+    pub samples: Option<String>, // directory with 0.wav-9.wav
+    pub wav: Option<String>, // render the game audio to this file
+    pub overlay: Profile,
+    pub bands: Vec<Band>, // replaces the profile's bands when given
+}
+
+pub const MACHINE_USAGE: &str = "[--dips <config file>] [--ships 3-6] [--bonus 1000|1500] [--coin-info on|off] [--watchdog <frames>] [--on-error ignore|trap|halt] [--samples <dir>] [--wav <out.wav>] [--overlay none|classic|tv] [--band <top>-<bottom>[,<left>-<right>]=<colour>]...";
+
+impl MachineOptions {
+    // Take `arg` if it's one of ours, calling `value` for its argument.
+    // Ok(false) means it's for the caller to deal with.
+    pub fn parse_arg<F>(&mut self, arg: &str, mut value: F) -> Result<bool, String>
+    where
+        F: FnMut() -> Result<String, String>,
+    {
+        match arg {
+            "--dips" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(&path).map_err(|err| format!("Error reading {}: {}", path, err))?;
+                self.dip_switches = DipSwitches::from_config(&text)?;
+            }
+            "--ships" => self.dip_switches.set("ships", &value()?)?,
+            "--bonus" => self.dip_switches.set("bonus", &value()?)?,
+            "--coin-info" => self.dip_switches.set("coin_info", &value()?)?,
+            "--samples" => self.samples = Some(value()?),
+            "--wav" => self.wav = Some(value()?),
+            "--overlay" => self.overlay = value()?.parse()?,
+            "--band" => self.bands.push(value()?.parse()?),
+            "--watchdog" => {
+                let frames = value()?;
+                let cycles = frames
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|frames| frames.checked_mul(machine::CYCLES_PER_FRAME))
+                    .ok_or(format!("Invalid watchdog timeout: {}", frames))?;
+                self.watchdog_cycles = Some(cycles);
+            }
+            "--on-error" => self.error_policy = value()?.parse()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // A board set up the way the options say, with nothing loaded yet
+    pub fn machine(&self) -> State8080<SpaceInvaders> {
+        let mut state = State8080::new(SpaceInvaders::new());
+        // CPU errors are ignored unless asked otherwise, the same as the real
+        // chip, so ROMs that use the undocumented aliases still run
+        state.set_error_policy(self.error_policy);
+        state.bus.port.dip_switches = self.dip_switches;
+        if let Some(cycles) = self.watchdog_cycles {
+            state.bus.watchdog = Watchdog::new(cycles);
+        }
+        state
+    }
+
+    pub fn overlay(&self) -> Overlay {
+        let mut overlay = Overlay::new(self.overlay);
+        if !self.bands.is_empty() {
+            overlay.bands = self.bands.clone();
+        }
+        overlay
+    }
+
+    // The mixer and .wav to render the sound into, if there's a --wav
+    pub fn audio(&self) -> Result<Option<(Mixer, WavSink)>, String> {
+        match &self.wav {
+            Some(path) => audio::open_audio(Path::new(path), self.samples.as_deref().map(Path::new)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<MachineOptions, String> {
+        let mut options = MachineOptions::default();
+        let mut args = args.iter().map(|arg| arg.to_string());
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing argument for '{}'", arg));
+            if !options.parse_arg(&arg, &mut value)? {
+                return Err(format!("Unknown argument: {}", arg));
+            }
+        }
+        Ok(options)
+    }
+
+    #[test]
+    fn parses_machine_options() {
+        let options = parse(&["--ships", "5", "--watchdog", "10", "--on-error", "trap", "--band", "0-31=red"]).unwrap();
+        assert_eq!(options.dip_switches.ships, 5);
+        assert_eq!(options.watchdog_cycles, Some(10 * machine::CYCLES_PER_FRAME));
+        assert_eq!(options.error_policy, ErrorPolicy::Trap);
+        assert_eq!(options.overlay().bands, [Band::rows(0, 31, crate::overlay::RED)]);
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        for args in [&["--watchdog", "18446744073709551615"][..], &["--watchdog", "-1"], &["--ships"], &["--rom", "invaders"]] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
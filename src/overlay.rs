@@ -0,0 +1,195 @@
+use std::str::FromStr;
+
+// The cabinet's monitor is black and white, the colour came from strips of
+// cellophane stuck over the glass. Rows and columns here are in the rotated
+// picture the player sees, 224 wide by 256 tall with row 0 at the top.
+
+pub const RED: u32 = 0xFFFF2020;
+pub const GREEN: u32 = 0xFF20FF20;
+pub const BLUE: u32 = 0xFF2020FF;
+pub const CYAN: u32 = 0xFF20FFFF;
+pub const MAGENTA: u32 = 0xFFFF20FF;
+pub const YELLOW: u32 = 0xFFFFFF20;
+pub const WHITE: u32 = 0xFFFFFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Band {
+    pub top: usize, // rows, inclusive
+    pub bottom: usize,
+    pub left: usize, // columns, inclusive
+    pub right: usize,
+    pub colour: u32,
+}
+
+impl Band {
+    // A strip right across the screen
+    pub fn rows(top: usize, bottom: usize, colour: u32) -> Band {
+        Band {
+            top,
+            bottom,
+            left: 0,
+            right: usize::MAX,
+            colour,
+        }
+    }
+
+    fn contains(&self, row: usize, column: usize) -> bool {
+        (self.top..=self.bottom).contains(&row) && (self.left..=self.right).contains(&column)
+    }
+}
+
+// Parses `top-bottom=colour` or `top-bottom,left-right=colour`, the colour
+// being a name or RRGGBB hex
+impl FromStr for Band {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("Expected top-bottom[,left-right]=colour, got {}", s);
+        let (area, colour) = s.split_once('=').ok_or_else(bad)?;
+        let range = |text: &str| -> Result<(usize, usize), String> {
+            let (start, end) = text.split_once('-').ok_or_else(bad)?;
+            let start = start.trim().parse().map_err(|_| bad())?;
+            let end = end.trim().parse().map_err(|_| bad())?;
+            if start > end {
+                return Err(bad());
+            }
+            Ok((start, end))
+        };
+
+        let mut band = match area.split_once(',') {
+            Some((rows, columns)) => {
+                let (top, bottom) = range(rows)?;
+                let (left, right) = range(columns)?;
+                Band { top, bottom, left, right, colour: 0 }
+            }
+            None => {
+                let (top, bottom) = range(area)?;
+                Band::rows(top, bottom, 0)
+            }
+        };
+        band.colour = parse_colour(colour.trim())?;
+        Ok(band)
+    }
+}
+
+fn parse_colour(name: &str) -> Result<u32, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "red" => Ok(RED),
+        "green" => Ok(GREEN),
+        "blue" => Ok(BLUE),
+        "cyan" => Ok(CYAN),
+        "magenta" => Ok(MAGENTA),
+        "yellow" => Ok(YELLOW),
+        "white" => Ok(WHITE),
+        hex => match u32::from_str_radix(hex.trim_start_matches('#'), 16) {
+            Ok(rgb) if hex.trim_start_matches('#').len() == 6 => Ok(0xFF000000 | rgb),
+            _ => Err(format!("Unknown colour: {}", name)),
+        },
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    #[default]
+    None, // plain white
+    Classic, // the upright cabinet's red and green strips
+    Tv, // the colour TV conversions, a strip per row of invaders
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Profile::None),
+            "classic" => Ok(Profile::Classic),
+            "tv" => Ok(Profile::Tv),
+            _ => Err(format!("Unknown overlay: {}", s)),
+        }
+    }
+}
+
+// Colours lit pixels by whichever band they fall in, anything outside the
+// bands stays white
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Overlay {
+    pub bands: Vec<Band>,
+}
+
+impl Overlay {
+    pub fn new(profile: Profile) -> Overlay {
+        let bands = match profile {
+            Profile::None => Vec::new(),
+            Profile::Classic => vec![
+                Band::rows(32, 63, RED), // UFO
+                Band::rows(184, 239, GREEN), // shields and player
+                // reserve ships, the credits to the right stay white
+                Band { top: 240, bottom: 255, left: 16, right: 133, colour: GREEN },
+            ],
+            Profile::Tv => vec![
+                Band::rows(0, 31, CYAN), // scores
+                Band::rows(32, 63, RED),
+                Band::rows(64, 95, MAGENTA),
+                Band::rows(96, 127, YELLOW),
+                Band::rows(128, 159, CYAN),
+                Band::rows(160, 183, MAGENTA),
+                Band::rows(184, 239, GREEN),
+                Band::rows(240, 255, YELLOW),
+            ],
+        };
+        Overlay { bands }
+    }
+
+    // Tint a rotated ARGB frame `width` pixels across
+    pub fn apply(&self, buffer: &mut [u32], width: usize) {
+        if self.bands.is_empty() || width == 0 {
+            return;
+        }
+
+        for (row, line) in buffer.chunks_exact_mut(width).enumerate() {
+            for (column, pixel) in line.iter_mut().enumerate() {
+                if *pixel & 0x00FFFFFF == 0 {
+                    continue;
+                }
+                if let Some(band) = self.bands.iter().find(|band| band.contains(row, column)) {
+                    *pixel = band.colour;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_band() {
+        assert_eq!("32-63=red".parse(), Ok(Band::rows(32, 63, RED)));
+        assert_eq!(
+            " 240 - 255 , 16-133 = 20FF20".parse(),
+            Ok(Band { top: 240, bottom: 255, left: 16, right: 133, colour: 0xFF20FF20 })
+        );
+        assert_eq!("0-31=#00FFFF".parse::<Band>().map(|band| band.colour), Ok(0xFF00FFFF));
+
+        for bad in ["32-63", "63-32=red", "32=red", "a-b=red", "0-31,5=red", "0-31=pink", "0-31=FFF"] {
+            assert!(bad.parse::<Band>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parse_profile() {
+        assert_eq!("Classic".parse(), Ok(Profile::Classic));
+        assert_eq!("tv".parse(), Ok(Profile::Tv));
+        assert!("sepia".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn apply_tints_lit_pixels() {
+        let overlay = Overlay { bands: vec![Band { top: 1, bottom: 1, left: 1, right: 2, colour: RED }] };
+        let mut buffer = [WHITE, WHITE, WHITE, 0xFF000000, WHITE, WHITE];
+        overlay.apply(&mut buffer, 3);
+        // Only the lit pixels inside the band change
+        assert_eq!(buffer, [WHITE, WHITE, WHITE, 0xFF000000, RED, RED]);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::audio::{SoundSource, SAMPLE_RATE};
+use crate::machine::{
+    SOUND_EXTENDED_PLAY, SOUND_FLEET_1, SOUND_FLEET_2, SOUND_FLEET_3, SOUND_FLEET_4,
+    SOUND_INVADER_DIE, SOUND_PLAYER_DIE, SOUND_SHOT, SOUND_UFO, SOUND_UFO_HIT,
+};
+
+// Stand-ins for the discrete sound circuits on the board, for anyone without
+// the sample set. They don't try to model the analog parts, just get close
+// enough to recognise, and with no randomness the output is the same every run.
+
+const VOLUME: f32 = 6000.0;
+
+#[derive(Clone, Copy)]
+enum Sound {
+    Ufo,
+    Shot,
+    PlayerDie,
+    InvaderDie,
+    Fleet(f32), // march note frequency
+    UfoHit,
+    ExtendedPlay,
+}
+
+impl Sound {
+    // How long a one-shot plays for, in seconds. The UFO goes until released.
+    fn length(&self) -> Option<f32> {
+        match self {
+            Sound::Ufo => None,
+            Sound::Shot => Some(0.25),
+            Sound::PlayerDie => Some(1.0),
+            Sound::InvaderDie => Some(0.2),
+            Sound::Fleet(_) => Some(0.1),
+            Sound::UfoHit => Some(1.0),
+            Sound::ExtendedPlay => Some(1.0),
+        }
+    }
+}
+
+struct Channel {
+    sound: Sound,
+    latch: u8, // 1 for OUT 3, 2 for OUT 5
+    bit: u8,
+    was_set: bool,
+    position: Option<u32>, // samples since it started, None when quiet
+    phase: f32, // oscillator phase, 0..1
+}
+
+impl Channel {
+    fn new(sound: Sound, latch: u8, bit: u8) -> Channel {
+        Channel {
+            sound,
+            latch,
+            bit,
+            was_set: false,
+            position: None,
+            phase: 0.0,
+        }
+    }
+
+    // Advance the oscillator and return a square wave, -1 or 1
+    fn square(&mut self, frequency: f32) -> f32 {
+        self.phase = (self.phase + frequency / SAMPLE_RATE as f32).fract();
+        if self.phase < 0.5 { 1.0 } else { -1.0 }
+    }
+
+    fn next_sample(&mut self, noise: f32) -> f32 {
+        let position = match self.position {
+            Some(position) => position,
+            None => return 0.0,
+        };
+        let t = position as f32 / SAMPLE_RATE as f32;
+
+        if let Some(length) = self.sound.length() {
+            if t >= length {
+                self.position = None;
+                return 0.0;
+            }
+        }
+        self.position = Some(position + 1);
+
+        // Linear fade out over the length of the one-shots
+        let fade = self.sound.length().map_or(1.0, |length| 1.0 - t / length);
+
+        match self.sound {
+            // Warbles up and down a few times a second
+            Sound::Ufo => {
+                let sweep = (t * 6.0).fract();
+                let triangle = if sweep < 0.5 { sweep * 2.0 } else { 2.0 - sweep * 2.0 };
+                self.square(500.0 + 300.0 * triangle) * 0.4
+            }
+            // Falling sweep
+            Sound::Shot => self.square(1600.0 - 1300.0 * t / 0.25) * 0.5 * fade,
+            Sound::PlayerDie => noise * 0.7 * fade,
+            Sound::InvaderDie => noise * 0.6 * fade,
+            Sound::Fleet(frequency) => self.square(frequency) * 0.6,
+            // Falling tone with some crunch on top
+            Sound::UfoHit => (self.square(1000.0 - 500.0 * t) * 0.4 + noise * 0.3) * fade,
+            // Beeps eight times a second
+            Sound::ExtendedPlay => {
+                let gate = if (t * 8.0).fract() < 0.5 { 1.0 } else { 0.0 };
+                self.square(1200.0) * 0.4 * gate
+            }
+        }
+    }
+}
+
+pub struct Synthesizer {
+    channels: Vec<Channel>,
+    lfsr: u32, // noise source shared by the explosions
+}
+
+impl Synthesizer {
+    pub fn new() -> Synthesizer {
+        Synthesizer {
+            channels: vec![
+                Channel::new(Sound::Ufo, 1, SOUND_UFO),
+                Channel::new(Sound::Shot, 1, SOUND_SHOT),
+                Channel::new(Sound::PlayerDie, 1, SOUND_PLAYER_DIE),
+                Channel::new(Sound::InvaderDie, 1, SOUND_INVADER_DIE),
+                Channel::new(Sound::ExtendedPlay, 1, SOUND_EXTENDED_PLAY),
+                // The march walks down through these
+                Channel::new(Sound::Fleet(98.0), 2, SOUND_FLEET_1),
+                Channel::new(Sound::Fleet(87.0), 2, SOUND_FLEET_2),
+                Channel::new(Sound::Fleet(78.0), 2, SOUND_FLEET_3),
+                Channel::new(Sound::Fleet(73.0), 2, SOUND_FLEET_4),
+                Channel::new(Sound::UfoHit, 2, SOUND_UFO_HIT),
+            ],
+            lfsr: 1,
+        }
+    }
+
+    // 17 bit LFSR, the same kind of noise generator the real board uses
+    fn noise(&mut self) -> f32 {
+        let bit = (self.lfsr ^ (self.lfsr >> 3)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (bit << 16);
+        if self.lfsr & 1 != 0 { 1.0 } else { -1.0 }
+    }
+}
+
+impl Default for Synthesizer {
+    fn default() -> Self {
+        Synthesizer::new()
+    }
+}
+
+impl SoundSource for Synthesizer {
+    fn latch(&mut self, sound1: u8, sound2: u8) {
+        for channel in self.channels.iter_mut() {
+            let value = if channel.latch == 1 { sound1 } else { sound2 };
+            let set = value & channel.bit != 0;
+
+            if set && !channel.was_set {
+                channel.position = Some(0);
+            } else if !set && channel.sound.length().is_none() {
+                channel.position = None;
+            }
+            channel.was_set = set;
+        }
+    }
+
+    fn next_sample(&mut self) -> i32 {
+        let noise = self.noise();
+        let total: f32 = self.channels.iter_mut().map(|channel| channel.next_sample(noise)).sum();
+        (total * VOLUME) as i32
+    }
+}
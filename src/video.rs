@@ -0,0 +1,54 @@
+use crate::machine::{BYTES_PER_LINE, VBLANK_LINE};
+use crate::overlay::Overlay;
+
+// The monitor is mounted on its side, so the picture the player sees is the
+// VRAM image turned a quarter turn anticlockwise
+pub const SCREEN_WIDTH: usize = VBLANK_LINE as usize; // 224
+pub const SCREEN_HEIGHT: usize = BYTES_PER_LINE * 8; // 256
+
+const BLACK: u32 = 0xFF000000;
+const WHITE: u32 = 0xFFFFFFFF;
+
+// Turns VRAM into the upright ARGB picture, gels and all
+pub struct Video {
+    pub overlay: Overlay,
+    buffer: Vec<u32>,
+}
+
+impl Video {
+    pub fn new(overlay: Overlay) -> Video {
+        Video {
+            overlay,
+            buffer: vec![BLACK; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    // Decode a VRAM image (normally `Beam::screen`) into the frame buffer,
+    // SCREEN_WIDTH x SCREEN_HEIGHT pixels of 0xAARRGGBB
+    pub fn render(&mut self, vram: &[u8]) -> &[u32] {
+        for (line, row) in vram.chunks_exact(BYTES_PER_LINE).take(SCREEN_WIDTH).enumerate() {
+            for (byte_index, byte) in row.iter().enumerate() {
+                for bit in 0..8 {
+                    // Bit 0 is the lowest pixel on the upright screen
+                    let x = byte_index * 8 + bit;
+                    let pixel = if byte & (1 << bit) != 0 { WHITE } else { BLACK };
+                    self.buffer[(SCREEN_HEIGHT - 1 - x) * SCREEN_WIDTH + line] = pixel;
+                }
+            }
+        }
+
+        self.overlay.apply(&mut self.buffer, SCREEN_WIDTH);
+        &self.buffer
+    }
+
+    // The last frame rendered
+    pub fn frame(&self) -> &[u32] {
+        &self.buffer
+    }
+}
+
+impl Default for Video {
+    fn default() -> Self {
+        Video::new(Overlay::default())
+    }
+}
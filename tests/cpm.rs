@@ -0,0 +1,165 @@
+use emu_8080::cpm;
+
+const TPA_START: u16 = 0x0100; // where .COM files are loaded
+
+// MVI C,9 / LXI D,msg / CALL 5 / MVI C,2 / MVI E,'!' / CALL 5 / JMP 0
+// msg: "HELLO$"
+const HELLO: [u8; 24] = [
+    0x0E, 0x09, 0x11, 0x12, 0x01, 0xCD, 0x05, 0x00, 0x0E, 0x02, 0x1E, 0x21, 0xCD, 0x05, 0x00, 0xC3, 0x00, 0x00,
+    b'H', b'E', b'L', b'L', b'O', b'$',
+];
+
+fn run(program: &[u8], max_cycles: u64) -> (Result<u64, String>, String) {
+    let mut output = String::new();
+    let result = cpm::run_com(program, max_cycles, |c| output.push(c));
+    (result, output)
+}
+
+#[test]
+fn prints_through_the_bdos() {
+    let (result, output) = run(&HELLO, 1_000_000);
+    assert_eq!(output, "HELLO!");
+    // Three MVIs, two each of CALL and the stub's JMP and RET, the LXI and the final JMP
+    assert_eq!(result, Ok(7 * 3 + 17 * 2 + 10 * 2 + 10 * 2 + 10 + 10));
+}
+
+#[test]
+fn gives_up_on_a_program_that_never_exits() {
+    // JMP 0100
+    let (result, output) = run(&[0xC3, 0x00, 0x01], 10_000);
+    assert!(result.is_err());
+    assert_eq!(output, "");
+}
+
+#[test]
+fn rejects_a_string_with_no_terminator() {
+    // MVI C,9 / LXI D,2000 / CALL 5 / JMP 0, with nothing but zeros at 2000
+    let (result, output) = run(&[0x0E, 0x09, 0x11, 0x00, 0x20, 0xCD, 0x05, 0x00, 0xC3, 0x00, 0x00], 1_000_000);
+    assert!(result.is_err());
+    assert_eq!(output, "");
+}
+
+// One ALU check: A and the carry going in, the instruction(s) under test,
+// then the A and flags they should leave. None means the flag isn't checked.
+struct Case {
+    a: u8,
+    carry: bool,
+    op: &'static [u8],
+    result: u8,
+    z: Option<bool>,
+    s: Option<bool>,
+    p: Option<bool>,
+    cy: bool,
+}
+
+const fn case(a: u8, carry: bool, op: &'static [u8], result: u8, zsp: Option<(bool, bool, bool)>, cy: bool) -> Case {
+    match zsp {
+        Some((z, s, p)) => Case { a, carry, op, result, z: Some(z), s: Some(s), p: Some(p), cy },
+        None => Case { a, carry, op, result, z: None, s: None, p: None, cy },
+    }
+}
+
+const CASES: [Case; 18] = [
+    case(0x3A, false, &[0xC6, 0xC6], 0x00, Some((true, false, true)), true), // ADI C6
+    case(0x7F, true, &[0xCE, 0x00], 0x80, Some((false, true, false)), false), // ACI 00
+    case(0x05, false, &[0xD6, 0x07], 0xFE, Some((false, true, false)), true), // SUI 07
+    case(0x10, true, &[0xDE, 0x0F], 0x00, Some((true, false, true)), false), // SBI 0F
+    case(0xF0, true, &[0xE6, 0x3C], 0x30, Some((false, false, true)), false), // ANI 3C
+    case(0xFF, true, &[0xEE, 0xFF], 0x00, Some((true, false, true)), false), // XRI FF
+    case(0x01, true, &[0xF6, 0x02], 0x03, Some((false, false, true)), false), // ORI 02
+    case(0x40, false, &[0xFE, 0x41], 0x40, Some((false, true, true)), true), // CPI 41
+    case(0xFF, true, &[0x3C], 0x00, Some((true, false, true)), true), // INR A
+    case(0x00, false, &[0x3D], 0xFF, Some((false, true, true)), false), // DCR A
+    case(0x81, false, &[0x07], 0x03, None, true), // RLC
+    case(0x01, false, &[0x0F], 0x80, None, true), // RRC
+    case(0x80, false, &[0x17], 0x00, None, true), // RAL
+    case(0x01, true, &[0x1F], 0x80, None, true), // RAR
+    case(0x19, false, &[0xC6, 0x28, 0x27], 0x47, Some((false, false, true)), false), // ADI 28 / DAA
+    case(0x55, true, &[0x2F], 0xAA, None, true), // CMA
+    case(0x80, false, &[0x06, 0x80, 0x80], 0x00, Some((true, false, true)), true), // MVI B,80 / ADD B
+    case(0x42, true, &[0x97], 0x00, Some((true, false, true)), false), // SUB A
+];
+
+// Assemble CASES into a .COM that checks itself and prints "OK", or "FAIL"
+// and the letter of the first case that went wrong
+fn alu_program() -> Vec<u8> {
+    let mut code = vec![];
+    let mut fail_jumps = vec![];
+    let mut jump_to_fail = |code: &mut Vec<u8>, opcode: u8| {
+        code.push(opcode);
+        fail_jumps.push(code.len());
+        code.extend([0, 0]);
+    };
+
+    for (i, case) in CASES.iter().enumerate() {
+        code.extend([0x1E, b'A' + i as u8]); // MVI E,letter
+        code.extend([0x3E, case.a]); // MVI A,a
+        code.push(0x37); // STC
+        if !case.carry {
+            code.push(0x3F); // CMC
+        }
+        code.extend(case.op);
+        // Jump to the failure message whenever a flag is the wrong way round
+        if let Some(z) = case.z {
+            jump_to_fail(&mut code, if z { 0xC2 } else { 0xCA }); // JNZ / JZ
+        }
+        if let Some(s) = case.s {
+            jump_to_fail(&mut code, if s { 0xF2 } else { 0xFA }); // JP / JM
+        }
+        if let Some(p) = case.p {
+            jump_to_fail(&mut code, if p { 0xE2 } else { 0xEA }); // JPO / JPE
+        }
+        jump_to_fail(&mut code, if case.cy { 0xD2 } else { 0xDA }); // JNC / JC
+        code.extend([0xFE, case.result]); // CPI result
+        jump_to_fail(&mut code, 0xC2); // JNZ
+    }
+
+    // Everything passed: MVI C,9 / LXI D,ok / CALL 5 / JMP 0
+    let ok = TPA_START + code.len() as u16 + 11;
+    code.extend([0x0E, 0x09, 0x11, ok as u8, (ok >> 8) as u8, 0xCD, 0x05, 0x00, 0xC3, 0x00, 0x00]);
+    code.extend(b"OK$");
+
+    // MOV B,E / MVI C,9 / LXI D,fail / CALL 5 / MOV E,B / MVI C,2 / CALL 5 / JMP 0,
+    // B keeping the letter safe from the LXI
+    let fail = TPA_START + code.len() as u16;
+    let message = fail + 18;
+    code.extend([0x43, 0x0E, 0x09, 0x11, message as u8, (message >> 8) as u8, 0xCD, 0x05, 0x00]);
+    code.extend([0x58, 0x0E, 0x02, 0xCD, 0x05, 0x00, 0xC3, 0x00, 0x00]);
+    code.extend(b"FAIL $");
+
+    for at in fail_jumps {
+        code[at] = fail as u8;
+        code[at + 1] = (fail >> 8) as u8;
+    }
+    code
+}
+
+#[test]
+fn alu_and_flag_results() {
+    let (result, output) = run(&alu_program(), 1_000_000);
+    assert!(result.is_ok());
+    assert_eq!(output, "OK");
+}
+
+// The exercisers aren't ours to ship. Drop TST8080.COM or 8080PRE.COM into
+// tests/roms to have them run too, otherwise these pass without doing anything
+fn run_exerciser(name: &str, passed: &str) {
+    let path = format!("{}/tests/roms/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let Ok(program) = std::fs::read(&path) else {
+        eprintln!("{} not found, skipping", path);
+        return;
+    };
+    let (result, output) = run(&program, 1_000_000_000);
+    assert!(result.is_ok(), "{:?}\n{}", result, output);
+    assert!(output.contains(passed), "{}", output);
+}
+
+#[test]
+fn tst8080() {
+    run_exerciser("TST8080.COM", "CPU IS OPERATIONAL");
+}
+
+#[test]
+fn exerciser_8080pre() {
+    run_exerciser("8080PRE.COM", "8080 Preliminary tests complete");
+}